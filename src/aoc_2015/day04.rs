@@ -1,5 +1,9 @@
 use crypto::digest::Digest;
 use crypto::md5::Md5;
+use std::thread;
+
+const BATCH_SIZE: u64 = 20_000;
+const DIGEST_LEN: usize = 16;
 
 pub fn run(input: &str) {
     let part1 = solve(input.as_bytes(), 5);
@@ -7,25 +11,140 @@ pub fn run(input: &str) {
 
     let part2 = solve(input.as_bytes(), 6);
     println!("part 2: {}", part2);
+
+    let matches = Miner::new(input.as_bytes(), 5).first_n(5);
+    println!("first five-zero matches: {:?}", matches);
 }
 
-fn solve(prefix: &[u8], num_zeros: usize) -> u64 {
-    let mut i = 0;
-    let mut buf = Vec::new();
-    buf.extend_from_slice(prefix);
-    let mut md5 = Md5::new();
-    let desired_prefix = "0".repeat(num_zeros);
+pub struct Miner {
+    // md5 state after the prefix, cloned for every candidate
+    base: Md5,
+    zeros: usize,
+    threads: usize,
+}
+
+impl Miner {
+    pub fn new(prefix: &[u8], zeros: usize) -> Miner {
+        if zeros > DIGEST_LEN * 2 {
+            panic!("Digest has only {} nibbles, got {}", DIGEST_LEN * 2, zeros);
+        }
+        let mut base = Md5::new();
+        base.input(prefix);
+        let threads = thread::available_parallelism().map_or(1, |n| n.get());
+
+        Miner {
+            base,
+            zeros,
+            threads,
+        }
+    }
+
+    pub fn first_n(&self, count: usize) -> Vec<u64> {
+        let mut found: Vec<u64> = vec![];
+        let mut start = 0;
+
+        // every round covers one contiguous range per thread, so all numbers below the
+        // round's end are checked before deciding whether enough matches were found
+        while found.len() < count {
+            let workers: Vec<_> = (0..self.threads as u64)
+                .map(|worker| {
+                    let base = self.base;
+                    let zeros = self.zeros;
+                    let from = start + worker * BATCH_SIZE;
+                    thread::spawn(move || mine_range(base, zeros, from, from + BATCH_SIZE))
+                })
+                .collect();
+
+            for worker in workers {
+                found.extend(worker.join().expect("Miner thread panicked"));
+            }
+            start += self.threads as u64 * BATCH_SIZE;
+        }
+
+        found.sort_unstable();
+        found.truncate(count);
+        found
+    }
+}
+
+fn mine_range(base: Md5, zeros: usize, from: u64, to: u64) -> Vec<u64> {
+    let mut digits = [0; 20];
+    let mut digest = [0; DIGEST_LEN];
+
+    (from..to)
+        .filter(|&n| {
+            let mut md5 = base;
+            md5.input(write_decimal(n, &mut digits));
+            md5.result(&mut digest);
+            has_leading_zeros(&digest, zeros)
+        })
+        .collect()
+}
+
+fn write_decimal(mut n: u64, buf: &mut [u8; 20]) -> &[u8] {
+    let mut start = buf.len();
     loop {
-        buf.truncate(prefix.len());
-        buf.extend_from_slice(format!("{}", i).as_bytes());
-        md5.reset();
-
-        // check md5
-        md5.input(&buf);
-        let res = md5.result_str();
-        if res.starts_with(&desired_prefix) {
-            return i;
+        start -= 1;
+        buf[start] = b'0' + (n % 10) as u8;
+        n /= 10;
+        if n == 0 {
+            return &buf[start..];
         }
-        i += 1;
+    }
+}
+
+fn has_leading_zeros(digest: &[u8], zeros: usize) -> bool {
+    let full_bytes = zeros / 2;
+    let half_byte = match zeros % 2 {
+        0 => true,
+        _ => digest[full_bytes] >> 4 == 0,
+    };
+    digest[..full_bytes].iter().all(|&b| b == 0) && half_byte
+}
+
+fn solve(prefix: &[u8], num_zeros: usize) -> u64 {
+    Miner::new(prefix, num_zeros).first_n(1)[0]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_write_decimal() {
+        let mut buf = [0; 20];
+        assert_eq!(write_decimal(0, &mut buf), b"0");
+        assert_eq!(write_decimal(609043, &mut buf), b"609043");
+        assert_eq!(write_decimal(u64::MAX, &mut buf), b"18446744073709551615");
+    }
+
+    #[test]
+    fn test_has_leading_zeros() {
+        let digest = [0x00, 0x00, 0x0f, 0xff];
+        assert!(has_leading_zeros(&digest, 4));
+        assert!(has_leading_zeros(&digest, 5));
+        assert!(!has_leading_zeros(&digest, 6));
+    }
+
+    #[test]
+    fn test_solve() {
+        assert_eq!(solve(b"abcdef", 5), 609043);
+    }
+
+    #[test]
+    fn test_first_n_independent_of_threads() {
+        // given
+        let mut single = Miner::new(b"abcdef", 3);
+        single.threads = 1;
+        let mut many = Miner::new(b"abcdef", 3);
+        many.threads = 7;
+
+        // when
+        let expected = single.first_n(10);
+
+        // then
+        assert_eq!(many.first_n(10), expected);
+        assert_eq!(expected.len(), 10);
+        assert!(expected.windows(2).all(|w| w[0] < w[1]));
     }
 }
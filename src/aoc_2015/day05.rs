@@ -1,5 +1,6 @@
-use fancy_regex::Regex;
 use std::collections::HashMap;
+use std::fmt;
+use std::fmt::Formatter;
 
 pub fn run(input: &[String]) {
     let count = count_nice(input);
@@ -7,86 +8,316 @@ pub fn run(input: &[String]) {
 
     let count_v2 = count_nice_v2(input);
     println!("Num of v2 nice strs {}", count_v2);
+
+    let either = nice_rules().or(nice_rules_v2());
+    println!("Nice under either: {}", count_matching(&either, input));
+    let only_v1 = nice_rules().and(nice_rules_v2().negate());
+    println!("Nice only under v1: {}", count_matching(&only_v1, input));
+
+    let rules = nice_rules();
+    for (rule, count) in failure_counts(&rules, input) {
+        println!("{} naughty strs failed: {}", count, rule);
+    }
 }
 
-pub fn count_nice(lines: &[String]) -> usize {
-    let blacklist = Regex::new(r"ab|cd|pq|xy").unwrap();
+#[derive(Debug, Clone, PartialEq)]
+pub enum StringRule {
+    AtLeast { count: usize, set: Vec<char> },
+    Repeated { gap: usize },
+    RepeatedGram { len: usize },
+    Forbidden(Vec<String>),
+    All(Vec<StringRule>),
+    Any(Vec<StringRule>),
+    Not(Box<StringRule>),
+}
+
+impl StringRule {
+    pub fn at_least(count: usize, set: &str) -> StringRule {
+        StringRule::AtLeast {
+            count,
+            set: set.chars().collect(),
+        }
+    }
+
+    pub fn repeated(gap: usize) -> StringRule {
+        StringRule::Repeated { gap }
+    }
+
+    pub fn repeated_gram(len: usize) -> StringRule {
+        if len == 0 {
+            panic!("Repeated gram must have at least one char");
+        }
+        StringRule::RepeatedGram { len }
+    }
+
+    pub fn forbidden(substrings: &[&str]) -> StringRule {
+        StringRule::Forbidden(substrings.iter().map(|s| s.to_string()).collect())
+    }
+
+    pub fn and(self, other: StringRule) -> StringRule {
+        match self {
+            StringRule::All(mut rules) => {
+                rules.push(other);
+                StringRule::All(rules)
+            }
+            rule => StringRule::All(vec![rule, other]),
+        }
+    }
 
-    let count = lines
-        .iter()
-        .filter(|line| !blacklist.is_match(line).unwrap())
-        .filter(|line| has_vowels(line))
-        .filter(|line| repeated_letter(line))
-        .count();
+    pub fn or(self, other: StringRule) -> StringRule {
+        match self {
+            StringRule::Any(mut rules) => {
+                rules.push(other);
+                StringRule::Any(rules)
+            }
+            rule => StringRule::Any(vec![rule, other]),
+        }
+    }
+
+    pub fn negate(self) -> StringRule {
+        StringRule::Not(Box::new(self))
+    }
 
-    count
+    pub fn matches(&self, line: &str) -> bool {
+        self.check(line).is_ok()
+    }
+
+    // on failure returns the innermost rule responsible, descending only through `All`
+    pub fn check(&self, line: &str) -> Result<(), &StringRule> {
+        let chars: Vec<char> = line.chars().collect();
+        self.check_chars(line, &chars)
+    }
+
+    fn check_chars(&self, line: &str, chars: &[char]) -> Result<(), &StringRule> {
+        let passed = match self {
+            StringRule::All(rules) => {
+                return rules
+                    .iter()
+                    .try_for_each(|rule| rule.check_chars(line, chars));
+            }
+            StringRule::AtLeast { count, set } => {
+                chars.iter().filter(|c| set.contains(c)).count() >= *count
+            }
+            StringRule::Repeated { gap } => chars
+                .iter()
+                .zip(chars.iter().skip(gap + 1))
+                .any(|(a, b)| a == b),
+            StringRule::RepeatedGram { len } => has_repeated_gram(chars, *len),
+            StringRule::Forbidden(substrings) => !substrings.iter().any(|s| line.contains(s)),
+            StringRule::Any(rules) => rules
+                .iter()
+                .any(|rule| rule.check_chars(line, chars).is_ok()),
+            StringRule::Not(rule) => rule.check_chars(line, chars).is_err(),
+        };
+
+        match passed {
+            true => Ok(()),
+            false => Err(self),
+        }
+    }
 }
 
-pub fn count_nice_v2(lines: &[String]) -> usize {
-    let count = lines
-        .iter()
-        .filter(|line| has_separated_pair(line))
-        .filter(|line| has_non_overlapping_pairs(line))
-        .count();
+impl fmt::Display for StringRule {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            StringRule::AtLeast { count, set } => {
+                write!(
+                    f,
+                    "at least {} of {}",
+                    count,
+                    set.iter().collect::<String>()
+                )
+            }
+            StringRule::Repeated { gap } => write!(f, "letter repeated with gap {}", gap),
+            StringRule::RepeatedGram { len } => {
+                write!(f, "non-overlapping repeated {}-gram", len)
+            }
+            StringRule::Forbidden(substrings) => write!(f, "none of {}", substrings.join(", ")),
+            StringRule::All(rules) => write_joined(f, rules, " and "),
+            StringRule::Any(rules) => write_joined(f, rules, " or "),
+            StringRule::Not(rule) => write!(f, "not ({})", rule),
+        }
+    }
+}
 
-    count
+fn write_joined(f: &mut Formatter<'_>, rules: &[StringRule], separator: &str) -> fmt::Result {
+    for (i, rule) in rules.iter().enumerate() {
+        if i > 0 {
+            write!(f, "{}", separator)?;
+        }
+        write!(f, "({})", rule)?;
+    }
+    Ok(())
 }
 
-fn repeated_letter(line: &str) -> bool {
-    let mut prev = None;
-    for ch in line.chars() {
-        if prev.map_or(false, |prev| prev == ch) {
+fn has_repeated_gram(chars: &[char], len: usize) -> bool {
+    // the variant is public, so `repeated_gram` may have been bypassed
+    if len == 0 {
+        return false;
+    }
+    let mut first_seen: HashMap<&[char], usize> = HashMap::new();
+    for (position, gram) in chars.windows(len).enumerate() {
+        let first = *first_seen.entry(gram).or_insert(position);
+        if position - first >= len {
             return true;
         }
-        prev = Some(ch);
     }
     false
 }
 
-fn has_vowels(line: &str) -> bool {
-    let vowels = "aeiou";
-
-    let has_all = line
-        .chars()
-        .into_iter()
-        .filter(|c| vowels.contains(&c.to_string()))
-        .count()
-        >= 3;
+pub fn nice_rules() -> StringRule {
+    StringRule::forbidden(&["ab", "cd", "pq", "xy"])
+        .and(StringRule::at_least(3, "aeiou"))
+        .and(StringRule::repeated(0))
+}
 
-    has_all
+pub fn nice_rules_v2() -> StringRule {
+    StringRule::repeated(1).and(StringRule::repeated_gram(2))
 }
 
-fn has_separated_pair(line: &str) -> bool {
-    for triplet in line.chars().collect::<Vec<char>>().windows(3) {
-        let first = triplet[0];
-        let last = triplet[2];
+pub fn count_matching(rule: &StringRule, lines: &[String]) -> usize {
+    lines.iter().filter(|line| rule.matches(line)).count()
+}
 
-        if first == last {
-            return true;
+pub fn failure_counts<'a>(rule: &'a StringRule, lines: &[String]) -> Vec<(&'a StringRule, usize)> {
+    let mut counts: Vec<(&StringRule, usize)> = vec![];
+    for failed in lines.iter().filter_map(|line| rule.check(line).err()) {
+        match counts.iter_mut().find(|(r, _)| std::ptr::eq(*r, failed)) {
+            Some((_, count)) => *count += 1,
+            None => counts.push((failed, 1)),
         }
     }
-    false
+    counts
 }
 
-fn has_non_overlapping_pairs(line: &str) -> bool {
-    let mut positions = HashMap::new();
-    for (position, pair) in line.chars().collect::<Vec<char>>().windows(2).enumerate() {
-        let pair_str: String = pair.iter().collect();
+pub fn count_nice(lines: &[String]) -> usize {
+    count_matching(&nice_rules(), lines)
+}
 
-        if positions.contains_key(&pair_str) {
-            let dup_position = match positions.get(&pair_str) {
-                Some(position) => *position,
-                None => 100,
-            };
+pub fn count_nice_v2(lines: &[String]) -> usize {
+    count_matching(&nice_rules_v2(), lines)
+}
 
-            let position_difference = position - dup_position;
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::parse::test_utils::vec_of_strings;
 
-            if position_difference >= 2 {
-                return true;
-            }
-        }
+    #[test]
+    fn test_count_nice() {
+        // given
+        let input = vec_of_strings![
+            "ugknbfddgicrmopn",
+            "aaa",
+            "jchzalrnumimnmhp",
+            "haegwjzuvuyypxyu",
+            "dvszwmarrgswjxmb"
+        ];
 
-        positions.insert(pair_str, position);
+        // when
+        let result = count_nice(&input);
+
+        // then
+        assert_eq!(result, 2);
+    }
+
+    #[test]
+    fn test_count_nice_v2() {
+        // given
+        let input = vec_of_strings![
+            "qjhvhtzxzqqjkmpb",
+            "xxyxx",
+            "aaaa",
+            "uurcxstgmygtbstg",
+            "ieodomkazucvgmuy",
+            "aaa"
+        ];
+
+        // when
+        let result = count_nice_v2(&input);
+
+        // then
+        assert_eq!(result, 3);
+    }
+
+    #[test]
+    fn test_check_reports_failed_rule() {
+        // given
+        let rule = nice_rules();
+
+        // when
+        let results: Vec<String> = ["jchzalrnumimnmhp", "haegwjzuvuyypxyu", "dvszwmarrgswjxmb"]
+            .iter()
+            .map(|line| rule.check(line).unwrap_err().to_string())
+            .collect();
+
+        // then
+        assert_eq!(
+            results,
+            vec![
+                "letter repeated with gap 0",
+                "none of ab, cd, pq, xy",
+                "at least 3 of aeiou"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_combinators() {
+        // given
+        let rule = StringRule::repeated(2)
+            .or(StringRule::at_least(2, "z"))
+            .and(StringRule::forbidden(&["q"]).negate());
+
+        // when
+        let results: Vec<Result<(), String>> = ["abaq", "zzq", "abcaq", "zzz"]
+            .iter()
+            .map(|line| rule.check(line).map_err(|r| r.to_string()))
+            .collect();
+
+        // then
+        assert_eq!(
+            results,
+            vec![
+                Err("(letter repeated with gap 2) or (at least 2 of z)".to_string()),
+                Ok(()),
+                Ok(()),
+                Err("not (none of q)".to_string())
+            ]
+        );
+    }
+
+    #[test]
+    fn test_failure_counts() {
+        // given
+        let rule = nice_rules_v2();
+        let input = vec_of_strings!["aaa", "xyxy", "abcd", "qjhvhtzxzqqjkmpb"];
+
+        // when
+        let result: Vec<(String, usize)> = failure_counts(&rule, &input)
+            .into_iter()
+            .map(|(r, count)| (r.to_string(), count))
+            .collect();
+
+        // then
+        assert_eq!(
+            result,
+            vec![
+                ("non-overlapping repeated 2-gram".to_string(), 1),
+                ("letter repeated with gap 1".to_string(), 1)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_empty_gram_never_matches() {
+        // given
+        let rule = StringRule::RepeatedGram { len: 0 };
+
+        // when
+        let result = rule.check("aaaa").map_err(|r| r.to_string());
+
+        // then
+        assert_eq!(result, Err("non-overlapping repeated 0-gram".to_string()));
     }
-    false
 }
@@ -1,40 +1,139 @@
 use std::cmp::{max, min};
-
-pub fn run(input: &[String]) {
-    let cmds: Vec<Command> = input.iter().map(|line| parse_command(line)).collect();
-
-    let mut first_grid = BinaryGrid::new(1000, 1000);
-    first_grid.apply_commands(&cmds);
+use std::fmt;
+use std::fmt::Formatter;
+use std::io;
+use std::io::Write;
+use std::path::Path;
+
+use crate::common::data::export;
+
+pub fn run(input: &[String], export_dir: Option<&Path>) {
+    let cmds: Vec<Command> = input
+        .iter()
+        .map(|line| parse_command(line))
+        .collect::<Result<_, _>>()
+        .unwrap_or_else(|e| panic!("Invalid input: {}", e));
+    let (w, h) = grid_size(&cmds);
+
+    let mut first_grid = BinaryGrid::new(w, h);
+    apply_or_panic(&mut first_grid, &cmds);
     let count = first_grid.count(Light::On);
     println!("Got {} lights on", count);
 
-    let mut second_grid = ValueGrid::new(1000, 1000);
-    second_grid.apply_commands(&cmds);
-    let brightness = second_grid.birghtness();
+    let mut second_grid = ValueGrid::new(w, h);
+    apply_or_panic(&mut second_grid, &cmds);
+    let brightness = second_grid.brightness();
     println!("Got {} brightness", brightness);
+
+    let mut capped_grid = LightGrid::with_rule(w, h, CappedBrightnessRule { max: 10 });
+    apply_or_panic(&mut capped_grid, &cmds);
+    println!("Got {} brightness capped at 10", capped_grid.total());
+
+    let mut rgb_grid = LightGrid::with_rule(w, h, RgbRule);
+    apply_or_panic(&mut rgb_grid, &cmds);
+    println!("Got {} total RGB intensity", rgb_grid.total());
+
+    if let Some(dir) = export_dir {
+        export(dir, "aoc_2015_day06_lights.pgm", |out| {
+            first_grid.write_pgm(out)
+        });
+        export(dir, "aoc_2015_day06_brightness.pgm", |out| {
+            second_grid.write_pgm(out)
+        });
+    }
+}
+
+fn apply_or_panic<R: LightRule>(grid: &mut LightGrid<R>, cmds: &[Command]) {
+    grid.apply_commands(cmds)
+        .unwrap_or_else(|e| panic!("Cannot apply commands: {}", e));
 }
 
-#[derive(PartialEq)]
-enum Light {
+pub trait LightRule {
+    type State: Clone;
+
+    fn initial(&self) -> Self::State;
+    fn supports(&self, cmd: &CommandType) -> bool;
+    // only called with commands the rule supports
+    fn apply(&self, state: &mut Self::State, cmd: &CommandType);
+    fn value(&self, state: &Self::State) -> usize;
+}
+
+#[derive(PartialEq, Copy, Clone)]
+pub enum Light {
     Off = 0,
     On = 1,
 }
 
-struct BinaryGrid {
-    width: usize,
-    lights: Vec<Light>,
+#[derive(PartialEq, Eq, Copy, Clone, Debug)]
+pub struct Rgb {
+    r: u8,
+    g: u8,
+    b: u8,
 }
 
-struct ValueGrid {
+#[derive(Default)]
+pub struct BinaryRule;
+
+#[derive(Default)]
+pub struct BrightnessRule;
+
+pub struct CappedBrightnessRule {
+    max: usize,
+}
+
+pub struct RgbRule;
+
+struct CompressedGrid<T> {
     width: usize,
-    lights: Vec<usize>,
+    height: usize,
+    row_starts: Vec<usize>,
+    column_starts: Vec<usize>,
+    cells: Vec<Vec<T>>,
+}
+
+pub struct LightGrid<R: LightRule> {
+    rule: R,
+    grid: CompressedGrid<R::State>,
+}
+
+type BinaryGrid = LightGrid<BinaryRule>;
+type ValueGrid = LightGrid<BrightnessRule>;
+
+#[derive(PartialEq, Debug, Clone)]
+pub enum CommandType {
+    Off,
+    On,
+    Toggle,
+    Dim(usize),
+    Brighten(usize),
+    Paint(Rgb),
 }
 
 #[derive(PartialEq, Debug)]
-enum CommandType {
-    Off = 0,
-    On = 1,
-    Toggle = 2,
+pub enum CommandError {
+    UnknownCommand(String),
+    InvalidAmount(String),
+    InvalidColor(String),
+    InvalidRange(String),
+    InvalidCoords(String),
+    Unsupported(CommandType),
+    OutOfBounds((usize, usize), (usize, usize)),
+}
+
+impl fmt::Display for CommandError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            CommandError::UnknownCommand(cmd) => write!(f, "unknown command '{}'", cmd),
+            CommandError::InvalidAmount(amount) => write!(f, "invalid amount '{}'", amount),
+            CommandError::InvalidColor(color) => write!(f, "invalid color '{}'", color),
+            CommandError::InvalidRange(cmd) => write!(f, "invalid range in '{}'", cmd),
+            CommandError::InvalidCoords(coords) => write!(f, "invalid coords '{}'", coords),
+            CommandError::Unsupported(cmd) => write!(f, "{:?} is not supported by rule", cmd),
+            CommandError::OutOfBounds(from, to) => {
+                write!(f, "range {:?} -> {:?} is outside of the grid", from, to)
+            }
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -44,204 +143,383 @@ pub struct Command {
     to: (usize, usize),
 }
 
-impl BinaryGrid {
-    pub fn new(w: usize, h: usize) -> BinaryGrid {
-        let width = w;
-        let height = h;
+impl LightRule for BinaryRule {
+    type State = Light;
 
-        let lights = (0..width * height).map(|_i| Light::Off).collect();
+    fn initial(&self) -> Light {
+        Light::Off
+    }
 
-        BinaryGrid { width, lights }
+    fn supports(&self, cmd: &CommandType) -> bool {
+        matches!(
+            cmd,
+            CommandType::On | CommandType::Off | CommandType::Toggle
+        )
     }
 
-    pub fn apply_commands(&mut self, cmds: &[Command]) {
-        for cmd in cmds {
-            self.apply_command(cmd)
+    fn apply(&self, state: &mut Light, cmd: &CommandType) {
+        *state = match (cmd, *state) {
+            (CommandType::Toggle, Light::On) => Light::Off,
+            (CommandType::Toggle, Light::Off) => Light::On,
+            (CommandType::Off, _) => Light::Off,
+            (CommandType::On, _) => Light::On,
+            (cmd, _) => panic!("Binary lights do not support {:?}", cmd),
         }
     }
 
-    pub fn apply_command(&mut self, cmd: &Command) {
-        let start_row = min(cmd.from.0, cmd.to.0);
-        let end_row = max(cmd.from.0, cmd.to.0);
+    fn value(&self, state: &Light) -> usize {
+        *state as usize
+    }
+}
+
+impl LightRule for BrightnessRule {
+    type State = usize;
 
-        let start_column = min(cmd.from.1, cmd.to.1);
-        let end_column = max(cmd.from.1, cmd.to.1);
+    fn initial(&self) -> usize {
+        0
+    }
 
-        for x in start_row..=end_row {
-            for y in start_column..=end_column {
-                self.apply_to_coord(x, y, cmd);
-            }
-        }
+    fn supports(&self, cmd: &CommandType) -> bool {
+        !matches!(cmd, CommandType::Paint(_))
     }
 
-    pub fn apply_to_coord(&mut self, row: usize, column: usize, cmd: &Command) {
-        match cmd.cmd_type {
-            CommandType::Off => self.off(row, column),
-            CommandType::On => self.on(row, column),
-            CommandType::Toggle => self.toggle(row, column),
+    fn apply(&self, state: &mut usize, cmd: &CommandType) {
+        *state = match cmd {
+            CommandType::On => *state + 1,
+            CommandType::Off => state.saturating_sub(1),
+            CommandType::Toggle => *state + 2,
+            CommandType::Dim(n) => state.saturating_sub(*n),
+            CommandType::Brighten(n) => *state + n,
+            CommandType::Paint(_) => panic!("Brightness lights do not support {:?}", cmd),
         }
     }
 
-    pub fn get_index(&self, row: usize, column: usize) -> usize {
-        row * self.width + column
+    fn value(&self, state: &usize) -> usize {
+        *state
     }
+}
 
-    pub fn toggle(&mut self, row: usize, column: usize) {
-        let idx = self.get_index(row, column);
-        let light = &self.lights[idx];
+impl LightRule for CappedBrightnessRule {
+    type State = usize;
 
-        let new_light = match light {
-            Light::On => Light::Off,
-            Light::Off => Light::On,
-        };
-
-        self.lights[idx] = new_light;
+    fn initial(&self) -> usize {
+        0
     }
 
-    pub fn on(&mut self, row: usize, column: usize) {
-        let idx = self.get_index(row, column);
-        let new_light = Light::On;
-        self.lights[idx] = new_light
+    fn supports(&self, cmd: &CommandType) -> bool {
+        BrightnessRule.supports(cmd)
     }
 
-    pub fn off(&mut self, row: usize, column: usize) {
-        let idx = self.get_index(row, column);
-        let new_light = Light::Off;
-        self.lights[idx] = new_light
+    fn apply(&self, state: &mut usize, cmd: &CommandType) {
+        BrightnessRule.apply(state, cmd);
+        *state = min(*state, self.max);
     }
 
-    pub fn count(&self, light: Light) -> usize {
-        self.lights.iter().filter(|l| **l == light).count()
+    fn value(&self, state: &usize) -> usize {
+        *state
     }
 }
 
-impl ValueGrid {
-    pub fn new(w: usize, h: usize) -> ValueGrid {
-        let width = w;
-        let height = h;
+impl LightRule for RgbRule {
+    type State = Rgb;
 
-        let lights: Vec<usize> = (0..width * height).map(|_i| 0).collect();
+    fn initial(&self) -> Rgb {
+        Rgb::BLACK
+    }
 
-        ValueGrid { width, lights }
+    fn supports(&self, _cmd: &CommandType) -> bool {
+        true
     }
 
-    pub fn apply_commands(&mut self, cmds: &[Command]) {
-        for cmd in cmds {
-            self.apply_command(cmd)
+    fn apply(&self, state: &mut Rgb, cmd: &CommandType) {
+        *state = match cmd {
+            CommandType::On => Rgb::WHITE,
+            CommandType::Off => Rgb::BLACK,
+            CommandType::Toggle => state.map(|c| !c),
+            CommandType::Dim(n) => state.map(|c| c.saturating_sub(min(*n, 255) as u8)),
+            CommandType::Brighten(n) => state.map(|c| c.saturating_add(min(*n, 255) as u8)),
+            CommandType::Paint(color) => *color,
         }
     }
 
-    pub fn apply_command(&mut self, cmd: &Command) {
-        let start_row = min(cmd.from.0, cmd.to.0);
-        let end_row = max(cmd.from.0, cmd.to.0);
-
-        let start_column = min(cmd.from.1, cmd.to.1);
-        let end_column = max(cmd.from.1, cmd.to.1);
+    fn value(&self, state: &Rgb) -> usize {
+        state.intensity()
+    }
+}
 
-        for x in start_row..=end_row {
-            for y in start_column..=end_column {
-                self.apply_to_coord(x, y, cmd);
-            }
+impl Rgb {
+    const BLACK: Rgb = Rgb { r: 0, g: 0, b: 0 };
+    const WHITE: Rgb = Rgb {
+        r: 255,
+        g: 255,
+        b: 255,
+    };
+
+    fn map<F: Fn(u8) -> u8>(&self, f: F) -> Rgb {
+        Rgb {
+            r: f(self.r),
+            g: f(self.g),
+            b: f(self.b),
         }
     }
 
-    pub fn apply_to_coord(&mut self, row: usize, column: usize, cmd: &Command) {
-        match cmd.cmd_type {
-            CommandType::Off => self.off(row, column),
-            CommandType::On => self.on(row, column),
-            CommandType::Toggle => self.toggle(row, column),
+    fn intensity(&self) -> usize {
+        self.r as usize + self.g as usize + self.b as usize
+    }
+}
+
+impl<T: Clone> CompressedGrid<T> {
+    pub fn new(width: usize, height: usize, initial: T) -> CompressedGrid<T> {
+        CompressedGrid {
+            width,
+            height,
+            row_starts: vec![0],
+            column_starts: vec![0],
+            cells: vec![vec![initial]],
         }
     }
 
-    pub fn get_index(&self, row: usize, column: usize) -> usize {
-        row * self.width + column
+    pub fn contains(&self, (x, y): (usize, usize)) -> bool {
+        x < self.width && y < self.height
     }
 
-    pub fn toggle(&mut self, row: usize, column: usize) {
-        let idx = self.get_index(row, column);
-        let light = &self.lights[idx];
-        self.lights[idx] = light + 2;
+    // callers must check that both corners are inside the grid
+    pub fn apply<F>(&mut self, from: (usize, usize), to: (usize, usize), f: F)
+    where
+        F: Fn(&mut T),
+    {
+        // coordinates are (x, y), x picks the column and y the row
+        let start_row = min(from.1, to.1);
+        let end_row = max(from.1, to.1);
+        let start_column = min(from.0, to.0);
+        let end_column = max(from.0, to.0);
+        debug_assert!(self.contains(from) && self.contains(to));
+
+        let first_row = self.split_rows(start_row);
+        let last_row = self.split_rows(end_row + 1);
+        let first_column = self.split_columns(start_column);
+        let last_column = self.split_columns(end_column + 1);
+
+        for row in &mut self.cells[first_row..last_row] {
+            for cell in &mut row[first_column..last_column] {
+                f(cell);
+            }
+        }
     }
 
-    pub fn on(&mut self, row: usize, column: usize) {
-        let idx = self.get_index(row, column);
-        let light = &self.lights[idx];
-        self.lights[idx] = light + 1
+    pub fn sum<F>(&self, value: F) -> usize
+    where
+        F: Fn(&T) -> usize,
+    {
+        let row_spans = spans(&self.row_starts, self.height);
+        let column_spans: Vec<usize> = spans(&self.column_starts, self.width).collect();
+
+        row_spans
+            .zip(&self.cells)
+            .map(|(row_span, row)| {
+                row.iter()
+                    .zip(&column_spans)
+                    .map(|(cell, column_span)| value(cell) * row_span * column_span)
+                    .sum::<usize>()
+            })
+            .sum()
     }
 
-    pub fn off(&mut self, row: usize, column: usize) {
-        let idx = self.get_index(row, column);
-        let light = &self.lights[idx];
-        if light == &0 {
-            return;
+    fn split_rows(&mut self, at: usize) -> usize {
+        match self.row_starts.binary_search(&at) {
+            Ok(idx) => idx,
+            Err(idx) if at == self.height => idx,
+            Err(idx) => {
+                self.row_starts.insert(idx, at);
+                let split = self.cells[idx - 1].clone();
+                self.cells.insert(idx, split);
+                idx
+            }
         }
-        let new_light = light - 1;
-        self.lights[idx] = new_light;
     }
 
-    pub fn birghtness(&self) -> usize {
-        self.lights.iter().sum()
+    fn split_columns(&mut self, at: usize) -> usize {
+        match self.column_starts.binary_search(&at) {
+            Ok(idx) => idx,
+            Err(idx) if at == self.width => idx,
+            Err(idx) => {
+                self.column_starts.insert(idx, at);
+                for row in &mut self.cells {
+                    let split = row[idx - 1].clone();
+                    row.insert(idx, split);
+                }
+                idx
+            }
+        }
     }
 }
 
-pub fn parse_command(cmd: &str) -> Command {
-    let start_token = &cmd[..7];
+fn spans(starts: &[usize], end: usize) -> impl Iterator<Item = usize> + '_ {
+    starts
+        .iter()
+        .zip(starts.iter().skip(1).copied().chain(std::iter::once(end)))
+        .map(|(start, next)| next - start)
+}
 
-    match start_token {
-        "turn on" => parse_turn_on(cmd),
-        "turn of" => parse_turn_off(cmd),
-        "toggle " => parse_toggle(cmd),
-        _ => panic!("Could not match command {}", cmd),
+impl<R: LightRule + Default> LightGrid<R> {
+    pub fn new(w: usize, h: usize) -> LightGrid<R> {
+        LightGrid::with_rule(w, h, R::default())
     }
 }
 
-fn parse_turn_on(cmd: &str) -> Command {
-    let tokens: Vec<&str> = cmd.split(' ').collect();
-    let from = &tokens[2];
-    let to = &tokens[4];
+impl<R: LightRule> LightGrid<R> {
+    pub fn with_rule(w: usize, h: usize, rule: R) -> LightGrid<R> {
+        let grid = CompressedGrid::new(w, h, rule.initial());
+        LightGrid { rule, grid }
+    }
+
+    // commands are checked up front, so a rejected one leaves the grid untouched
+    pub fn apply_commands(&mut self, cmds: &[Command]) -> Result<(), CommandError> {
+        cmds.iter().try_for_each(|cmd| self.check(cmd))?;
+        cmds.iter().try_for_each(|cmd| self.apply_command(cmd))
+    }
 
-    Command {
-        cmd_type: CommandType::On,
-        from: parse_coords(from),
-        to: parse_coords(to),
+    pub fn apply_command(&mut self, cmd: &Command) -> Result<(), CommandError> {
+        self.check(cmd)?;
+        let rule = &self.rule;
+        self.grid
+            .apply(cmd.from, cmd.to, |light| rule.apply(light, &cmd.cmd_type));
+        Ok(())
     }
-}
 
-fn parse_coords(coords: &str) -> (usize, usize) {
-    let split: Vec<&str> = coords.split(',').collect();
-    let x = split[0].parse::<usize>().unwrap();
-    let y = split[1].parse::<usize>().unwrap();
+    fn check(&self, cmd: &Command) -> Result<(), CommandError> {
+        if !self.rule.supports(&cmd.cmd_type) {
+            return Err(CommandError::Unsupported(cmd.cmd_type.clone()));
+        }
+        if !self.grid.contains(cmd.from) || !self.grid.contains(cmd.to) {
+            return Err(CommandError::OutOfBounds(cmd.from, cmd.to));
+        }
+        Ok(())
+    }
 
-    (x, y)
+    pub fn total(&self) -> usize {
+        self.grid.sum(|light| self.rule.value(light))
+    }
+
+    pub fn write_pgm<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        let grid = &self.grid;
+        let max_value = grid
+            .cells
+            .iter()
+            .flatten()
+            .map(|light| self.rule.value(light))
+            .max()
+            .unwrap_or(0);
+        let shade = |light: &R::State| match max_value {
+            0 => 0,
+            _ => (self.rule.value(light) * 255 / max_value) as u8,
+        };
+
+        write!(out, "P5\n{} {}\n255\n", grid.width, grid.height)?;
+
+        let column_spans: Vec<usize> = spans(&grid.column_starts, grid.width).collect();
+        for (row_span, row) in spans(&grid.row_starts, grid.height).zip(&grid.cells) {
+            let mut pixels: Vec<u8> = Vec::with_capacity(grid.width);
+            for (light, column_span) in row.iter().zip(&column_spans) {
+                pixels.resize(pixels.len() + column_span, shade(light));
+            }
+            for _ in 0..row_span {
+                out.write_all(&pixels)?;
+            }
+        }
+
+        Ok(())
+    }
 }
 
-fn parse_turn_off(cmd: &str) -> Command {
-    let tokens: Vec<&str> = cmd.split(' ').collect();
-    let from = &tokens[2];
-    let to = &tokens[4];
+impl LightGrid<BinaryRule> {
+    pub fn count(&self, light: Light) -> usize {
+        self.grid.sum(|l| (*l == light).into())
+    }
+}
 
-    Command {
-        cmd_type: CommandType::Off,
-        from: parse_coords(from),
-        to: parse_coords(to),
+impl LightGrid<BrightnessRule> {
+    pub fn brightness(&self) -> usize {
+        self.total()
     }
 }
 
-fn parse_toggle(cmd: &str) -> Command {
-    let tokens: Vec<&str> = cmd.split(' ').collect();
-    let from = tokens[1];
-    let to = tokens[3];
+fn grid_size(cmds: &[Command]) -> (usize, usize) {
+    let max_column = cmds.iter().map(|c| max(c.from.0, c.to.0)).max();
+    let max_row = cmds.iter().map(|c| max(c.from.1, c.to.1)).max();
+
+    (
+        max(1000, max_column.map_or(0, |c| c + 1)),
+        max(1000, max_row.map_or(0, |r| r + 1)),
+    )
+}
 
-    Command {
-        cmd_type: CommandType::Toggle,
-        from: parse_coords(from),
-        to: parse_coords(to),
+pub fn parse_command(cmd: &str) -> Result<Command, CommandError> {
+    let tokens: Vec<&str> = cmd.split_whitespace().collect();
+
+    let (cmd_type, range) = match tokens.as_slice() {
+        ["turn", "on", range @ ..] => (CommandType::On, range),
+        ["turn", "off", range @ ..] => (CommandType::Off, range),
+        ["toggle", range @ ..] => (CommandType::Toggle, range),
+        ["dim", n, range @ ..] => (CommandType::Dim(parse_amount(n)?), range),
+        ["brighten", n, range @ ..] => (CommandType::Brighten(parse_amount(n)?), range),
+        ["paint", color, range @ ..] => (CommandType::Paint(parse_color(color)?), range),
+        _ => return Err(CommandError::UnknownCommand(cmd.to_string())),
+    };
+
+    match range {
+        [from, "through", to] => Ok(Command {
+            cmd_type,
+            from: parse_coords(from)?,
+            to: parse_coords(to)?,
+        }),
+        _ => Err(CommandError::InvalidRange(cmd.to_string())),
     }
 }
 
+fn parse_amount(amount: &str) -> Result<usize, CommandError> {
+    amount
+        .parse()
+        .map_err(|_| CommandError::InvalidAmount(amount.to_string()))
+}
+
+fn parse_color(color: &str) -> Result<Rgb, CommandError> {
+    let invalid = || CommandError::InvalidColor(color.to_string());
+    let hex = color
+        .strip_prefix('#')
+        .filter(|h| h.len() == 6 && h.is_ascii())
+        .ok_or_else(invalid)?;
+    let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).map_err(|_| invalid());
+
+    Ok(Rgb {
+        r: channel(0)?,
+        g: channel(2)?,
+        b: channel(4)?,
+    })
+}
+
+fn parse_coords(coords: &str) -> Result<(usize, usize), CommandError> {
+    let invalid = || CommandError::InvalidCoords(coords.to_string());
+    let (x, y) = coords.split_once(',').ok_or_else(invalid)?;
+    let x = x.parse::<usize>().map_err(|_| invalid())?;
+    let y = y.parse::<usize>().map_err(|_| invalid())?;
+
+    Ok((x, y))
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::aoc_2015::day06::{BinaryGrid, Command, CommandType, Light};
+    use crate::aoc_2015::day06::{
+        parse_command, BinaryGrid, CappedBrightnessRule, Command, CommandError, CommandType, Light,
+        LightGrid, Rgb, RgbRule, ValueGrid,
+    };
+
+    fn parse_all(input: &[&str]) -> Vec<Command> {
+        input
+            .iter()
+            .map(|line| parse_command(line).unwrap())
+            .collect()
+    }
 
     #[test]
     fn test_all_on_cmd() {
@@ -256,10 +534,276 @@ mod tests {
         };
 
         // when
-        grid.apply_command(&cmd);
+        grid.apply_command(&cmd).unwrap();
         let count = grid.count(Light::On);
         // then
         let expected = w * h;
         assert_eq!(count, expected);
     }
+
+    #[test]
+    fn test_binary_grid_example() {
+        // given
+        let cmds = parse_all(&[
+            "turn on 0,0 through 999,999",
+            "toggle 0,0 through 999,0",
+            "turn off 499,499 through 500,500",
+        ]);
+        let mut grid = BinaryGrid::new(1000, 1000);
+
+        // when
+        grid.apply_commands(&cmds).unwrap();
+
+        // then
+        assert_eq!(grid.count(Light::On), 1_000_000 - 1000 - 4);
+        assert_eq!(grid.count(Light::Off), 1000 + 4);
+    }
+
+    #[test]
+    fn test_value_grid_example() {
+        // given
+        let cmds = parse_all(&[
+            "turn on 0,0 through 0,0",
+            "toggle 0,0 through 999,999",
+            "turn off 0,0 through 1,1",
+            "turn off 0,0 through 1,1",
+        ]);
+        let mut grid = ValueGrid::new(1000, 1000);
+
+        // when
+        grid.apply_commands(&cmds).unwrap();
+
+        // then
+        assert_eq!(grid.brightness(), 1 + 2_000_000 - 2 - 3 * 2);
+    }
+
+    #[test]
+    fn test_huge_sparse_grid() {
+        // given
+        let size = 1_000_000;
+        let cmds = parse_all(&[
+            "turn on 0,0 through 999999,999999",
+            "toggle 10,10 through 999989,999989",
+            "turn off 500000,0 through 500000,999999",
+            "toggle 0,0 through 0,0",
+        ]);
+        let mut binary = BinaryGrid::new(size, size);
+        let mut value = ValueGrid::new(size, size);
+
+        // when
+        binary.apply_commands(&cmds).unwrap();
+        value.apply_commands(&cmds).unwrap();
+
+        // then
+        let inner = 999_980 * 999_980;
+        let outer = size * size - inner;
+        assert_eq!(binary.count(Light::On), outer - 20 - 1);
+        assert_eq!(value.brightness(), outer + 3 * inner - size + 2);
+    }
+
+    #[test]
+    fn test_parse_extended_commands() {
+        // given
+        let input = ["dim 3 0,0 through 5,5", "paint #ff8000 1,2 through 3,4"];
+
+        // when
+        let cmds = parse_all(&input);
+
+        // then
+        assert_eq!(cmds[0].cmd_type, CommandType::Dim(3));
+        assert_eq!((cmds[0].from, cmds[0].to), ((0, 0), (5, 5)));
+        let orange = Rgb {
+            r: 255,
+            g: 128,
+            b: 0,
+        };
+        assert_eq!(cmds[1].cmd_type, CommandType::Paint(orange));
+        assert_eq!((cmds[1].from, cmds[1].to), ((1, 2), (3, 4)));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let cases = [
+            (
+                "switch on 0,0 through 1,1",
+                CommandError::UnknownCommand("switch on 0,0 through 1,1".to_string()),
+            ),
+            (
+                "dim x 0,0 through 1,1",
+                CommandError::InvalidAmount("x".to_string()),
+            ),
+            (
+                "paint #12345g 0,0 through 1,1",
+                CommandError::InvalidColor("#12345g".to_string()),
+            ),
+            (
+                "toggle 0,0 to 1,1",
+                CommandError::InvalidRange("toggle 0,0 to 1,1".to_string()),
+            ),
+            (
+                "toggle 0,0 through 1",
+                CommandError::InvalidCoords("1".to_string()),
+            ),
+        ];
+
+        for (input, expected) in cases {
+            assert_eq!(parse_command(input).unwrap_err(), expected, "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_dim_and_brighten() {
+        // given
+        let cmds = parse_all(&[
+            "brighten 5 0,0 through 9,9",
+            "dim 3 0,0 through 4,9",
+            "dim 3 0,0 through 1,9",
+        ]);
+        let mut value = ValueGrid::new(10, 10);
+
+        // when
+        value.apply_commands(&cmds).unwrap();
+
+        // then
+        assert_eq!(value.brightness(), 50 * 5 + 30 * 2);
+    }
+
+    #[test]
+    fn test_binary_rule_rejects_levels_and_colors() {
+        // given
+        let cmds = parse_all(&["turn on 0,0 through 9,9", "dim 1 0,0 through 9,9"]);
+        let paint = parse_all(&["paint #000000 0,0 through 9,9"]);
+        let mut binary = BinaryGrid::new(10, 10);
+
+        // when
+        let dim = binary.apply_commands(&cmds);
+        let painted = binary.apply_command(&paint[0]);
+
+        // then
+        assert_eq!(dim, Err(CommandError::Unsupported(CommandType::Dim(1))));
+        assert_eq!(
+            painted,
+            Err(CommandError::Unsupported(CommandType::Paint(Rgb::BLACK)))
+        );
+        assert_eq!(binary.count(Light::On), 0);
+    }
+
+    #[test]
+    fn test_brightness_rules_reject_colors() {
+        // given
+        let cmds = parse_all(&["turn on 0,0 through 9,9", "paint #ffffff 0,0 through 9,9"]);
+        let mut value = ValueGrid::new(10, 10);
+        let mut capped = LightGrid::with_rule(10, 10, CappedBrightnessRule { max: 4 });
+
+        // when
+        let value_result = value.apply_commands(&cmds);
+        let capped_result = capped.apply_commands(&cmds);
+
+        // then
+        let expected = Err(CommandError::Unsupported(CommandType::Paint(Rgb::WHITE)));
+        assert_eq!(value_result, expected);
+        assert_eq!(capped_result, expected);
+        assert_eq!(value.brightness(), 0);
+        assert_eq!(capped.total(), 0);
+    }
+
+    #[test]
+    fn test_out_of_bounds_leaves_grid_untouched() {
+        // given
+        let cmds = parse_all(&["turn on 0,0 through 9,9", "toggle 5,5 through 10,9"]);
+        let mut binary = BinaryGrid::new(10, 10);
+
+        // when
+        let batch = binary.apply_commands(&cmds);
+        let single = binary.apply_command(&cmds[1]);
+
+        // then
+        let expected = Err(CommandError::OutOfBounds((5, 5), (10, 9)));
+        assert_eq!(batch, expected);
+        assert_eq!(single, expected);
+        assert_eq!(binary.count(Light::On), 0);
+    }
+
+    #[test]
+    fn test_capped_brightness() {
+        // given
+        let cmds = parse_all(&[
+            "toggle 0,0 through 9,9",
+            "toggle 0,0 through 9,9",
+            "turn on 0,0 through 4,4",
+            "turn off 0,0 through 0,0",
+        ]);
+        let mut grid = LightGrid::with_rule(10, 10, CappedBrightnessRule { max: 4 });
+
+        // when
+        grid.apply_commands(&cmds).unwrap();
+
+        // then
+        assert_eq!(grid.total(), 100 * 4 - 1);
+    }
+
+    #[test]
+    fn test_rgb_lights() {
+        // given
+        let cmds = parse_all(&[
+            "paint #102030 0,0 through 1,1",
+            "toggle 0,0 through 0,1",
+            "dim 16 0,0 through 1,0",
+        ]);
+        let mut grid = LightGrid::with_rule(2, 2, RgbRule);
+
+        // when
+        grid.apply_commands(&cmds).unwrap();
+
+        // then
+        let toggled = 0xef + 0xdf + 0xcf;
+        let dimmed = 0x10 + 0x20;
+        assert_eq!(
+            grid.total(),
+            (toggled - 48) + toggled + dimmed + (0x10 + 0x20 + 0x30)
+        );
+    }
+
+    #[test]
+    fn test_write_pgm() {
+        // given
+        let cmds = parse_all(&["turn on 1,0 through 2,1", "toggle 0,1 through 1,1"]);
+        let mut binary = BinaryGrid::new(3, 2);
+        let mut value = ValueGrid::new(3, 2);
+        binary.apply_commands(&cmds).unwrap();
+        value.apply_commands(&cmds).unwrap();
+
+        // when
+        let mut binary_pgm: Vec<u8> = vec![];
+        binary.write_pgm(&mut binary_pgm).unwrap();
+        let mut value_pgm: Vec<u8> = vec![];
+        value.write_pgm(&mut value_pgm).unwrap();
+
+        // then
+        let header = b"P5\n3 2\n255\n".to_vec();
+        let binary_pixels = [0, 255, 255, 255, 0, 255];
+        let value_pixels = [0, 85, 85, 170, 255, 85];
+        assert_eq!(
+            binary_pgm,
+            [header.clone(), binary_pixels.to_vec()].concat()
+        );
+        assert_eq!(value_pgm, [header, value_pixels.to_vec()].concat());
+    }
+
+    #[test]
+    fn test_write_pgm_non_square() {
+        // given
+        let cmds = parse_all(&["turn on 4,0 through 4,0", "turn on 0,1 through 0,1"]);
+        let mut grid = BinaryGrid::new(5, 2);
+        grid.apply_commands(&cmds).unwrap();
+
+        // when
+        let mut pgm: Vec<u8> = vec![];
+        grid.write_pgm(&mut pgm).unwrap();
+
+        // then
+        let header = b"P5\n5 2\n255\n".to_vec();
+        let pixels = [0, 0, 0, 0, 255, 255, 0, 0, 0, 0];
+        assert_eq!(pgm, [header, pixels.to_vec()].concat());
+    }
 }
@@ -1,108 +1,637 @@
-use std::collections::{HashMap, HashSet};
-use std::iter::FromIterator;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt;
+use std::fmt::Formatter;
+use std::str::FromStr;
 
 pub fn run(input: &[String]) {
-    let mut solver = SignalSolver::new();
-    solver.solve(input, "a")
+    let mut circuit = match parse_circuit(input) {
+        Ok(circuit) => circuit,
+        Err(e) => panic!("Invalid circuit: {}", e),
+    };
+
+    let a = circuit.eval("a").unwrap();
+    println!("Signal on wire a: {}", a);
+
+    let cone = circuit.simplify("a", &["b"]).unwrap();
+    println!(
+        "Wire a depends on {} of {} wires with b left symbolic",
+        cone.wire_count(),
+        circuit.wire_count()
+    );
+    let folded = circuit.simplify("a", &[]).unwrap();
+    println!("Wire a folds down to:\n{}", folded.to_dot());
+
+    circuit.override_wire("b", a).unwrap();
+    let overridden = circuit.eval("a").unwrap();
+    println!("Signal on wire a after overriding b: {}", overridden);
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Operand {
+    Signal(u16),
+    Wire(String),
 }
 
-struct SignalSolver {
-    lookup: HashMap<String, u16>,
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Gate {
+    Buffer(Operand),
+    Not(Operand),
+    And(Operand, Operand),
+    Or(Operand, Operand),
+    LShift(Operand, Operand),
+    RShift(Operand, Operand),
 }
 
-impl SignalSolver {
-    pub fn new() -> SignalSolver {
-        let lookup: HashMap<String, u16> = HashMap::new();
+#[derive(Debug, PartialEq, Eq)]
+pub enum CircuitError {
+    InvalidInstruction(String),
+    DuplicateWire(String),
+    UndefinedWires(Vec<String>),
+    Cycle(Vec<String>),
+}
+
+pub struct Circuit {
+    gates: HashMap<String, Gate>,
+    order: Vec<String>,
+    position: HashMap<String, usize>,
+    dependents: HashMap<String, Vec<String>>,
+    overrides: HashMap<String, u16>,
+    signals: HashMap<String, u16>,
+}
 
-        SignalSolver { lookup }
+impl Operand {
+    fn wire(&self) -> Option<&str> {
+        match self {
+            Operand::Signal(_) => None,
+            Operand::Wire(name) => Some(name),
+        }
     }
 
-    pub fn solve(&mut self, input: &[String], unknown: &str) {
-        let mut unsolved: HashSet<&String> = HashSet::from_iter(input.iter());
+    fn signal(&self) -> Option<u16> {
+        match self {
+            Operand::Signal(signal) => Some(*signal),
+            Operand::Wire(_) => None,
+        }
+    }
+}
 
-        println!("{:?}", unsolved);
-        let mut iter_num = 1;
-        loop {
-            println!("Iteration: {}", iter_num);
-            iter_num += 1;
-            let mut to_remove: Vec<&String> = vec![];
+impl fmt::Display for Operand {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Operand::Signal(signal) => write!(f, "{}", signal),
+            Operand::Wire(wire) => write!(f, "{}", wire),
+        }
+    }
+}
 
-            for line in &unsolved {
-                let separator = "->";
-                let tokens: Vec<&str> = line.trim().split(separator).collect();
+impl FromStr for Operand {
+    type Err = CircuitError;
 
-                let input = tokens[0];
-                let output_signal = tokens[1].trim();
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Ok(signal) = s.parse::<u16>() {
+            return Ok(Operand::Signal(signal));
+        }
 
-                let result = self.eval(input);
+        if !s.is_empty() && s.chars().all(|c| c.is_ascii_lowercase()) {
+            Ok(Operand::Wire(s.to_string()))
+        } else {
+            Err(CircuitError::InvalidInstruction(s.to_string()))
+        }
+    }
+}
 
-                if let Some(r) = result {
-                    println!("Solved {}={}", output_signal, r);
-                    self.lookup.insert(output_signal.to_string(), r);
-                    to_remove.push(line);
-                }
+impl Gate {
+    pub fn operands(&self) -> Vec<&Operand> {
+        match self {
+            Gate::Buffer(x) | Gate::Not(x) => vec![x],
+            Gate::And(x, y) | Gate::Or(x, y) | Gate::LShift(x, y) | Gate::RShift(x, y) => {
+                vec![x, y]
             }
+        }
+    }
 
-            for el in to_remove {
-                unsolved.remove(el);
-            }
+    pub fn inputs(&self) -> impl Iterator<Item = &str> {
+        self.operands().into_iter().filter_map(|op| op.wire())
+    }
+
+    fn eval<F>(&self, resolve: F) -> u16
+    where
+        F: Fn(&Operand) -> u16,
+    {
+        match self {
+            Gate::Buffer(x) => resolve(x),
+            Gate::Not(x) => !resolve(x),
+            Gate::And(x, y) => resolve(x) & resolve(y),
+            Gate::Or(x, y) => resolve(x) | resolve(y),
+            Gate::LShift(x, y) => resolve(x).checked_shl(resolve(y) as u32).unwrap_or(0),
+            Gate::RShift(x, y) => resolve(x).checked_shr(resolve(y) as u32).unwrap_or(0),
+        }
+    }
+
+    fn map_operands<F>(&self, f: F) -> Gate
+    where
+        F: Fn(&Operand) -> Operand,
+    {
+        match self {
+            Gate::Buffer(x) => Gate::Buffer(f(x)),
+            Gate::Not(x) => Gate::Not(f(x)),
+            Gate::And(x, y) => Gate::And(f(x), f(y)),
+            Gate::Or(x, y) => Gate::Or(f(x), f(y)),
+            Gate::LShift(x, y) => Gate::LShift(f(x), f(y)),
+            Gate::RShift(x, y) => Gate::RShift(f(x), f(y)),
+        }
+    }
+
+    fn fold(self) -> Gate {
+        use Operand::Signal;
+
+        if self.inputs().next().is_none() {
+            let value = self.eval(|op| op.signal().unwrap_or_default());
+            return Gate::Buffer(Signal(value));
+        }
 
-            if let Some(s) = self.lookup.get(unknown) {
-                println!("Found solution! {} = {}", unknown, s);
-                break;
+        match self {
+            Gate::And(Signal(0), _) | Gate::And(_, Signal(0)) => Gate::Buffer(Signal(0)),
+            Gate::Or(Signal(u16::MAX), _) | Gate::Or(_, Signal(u16::MAX)) => {
+                Gate::Buffer(Signal(u16::MAX))
             }
+            Gate::And(Signal(u16::MAX), x)
+            | Gate::And(x, Signal(u16::MAX))
+            | Gate::Or(Signal(0), x)
+            | Gate::Or(x, Signal(0))
+            | Gate::LShift(x, Signal(0))
+            | Gate::RShift(x, Signal(0)) => Gate::Buffer(x),
+            Gate::LShift(Signal(0), _) | Gate::RShift(Signal(0), _) => Gate::Buffer(Signal(0)),
+            Gate::LShift(_, Signal(shift)) | Gate::RShift(_, Signal(shift)) if shift >= 16 => {
+                Gate::Buffer(Signal(0))
+            }
+            gate => gate,
+        }
+    }
+}
+
+impl fmt::Display for Gate {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Gate::Buffer(x) => write!(f, "{}", x),
+            Gate::Not(x) => write!(f, "NOT {}", x),
+            Gate::And(x, y) => write!(f, "{} AND {}", x, y),
+            Gate::Or(x, y) => write!(f, "{} OR {}", x, y),
+            Gate::LShift(x, y) => write!(f, "{} LSHIFT {}", x, y),
+            Gate::RShift(x, y) => write!(f, "{} RSHIFT {}", x, y),
         }
     }
+}
+
+impl FromStr for Gate {
+    type Err = CircuitError;
 
-    pub fn eval(&self, input: &str) -> Option<u16> {
-        let tokens: Vec<&str> = input.trim().split(' ').collect();
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let tokens: Vec<&str> = s.split_whitespace().collect();
 
-        return match tokens.len() {
-            3 => self.eval_double(tokens[0].trim(), tokens[1].trim(), tokens[2].trim()),
-            2 => self.eval_single(tokens[1].trim(), tokens[0].trim()),
-            1 => self.eval_direct(tokens[0].trim()),
-            _ => panic!("Cannot eval input: {}", input),
+        let gate = match tokens.as_slice() {
+            [x] => Gate::Buffer(x.parse()?),
+            ["NOT", x] => Gate::Not(x.parse()?),
+            [x, "AND", y] => Gate::And(x.parse()?, y.parse()?),
+            [x, "OR", y] => Gate::Or(x.parse()?, y.parse()?),
+            [x, "LSHIFT", y] => Gate::LShift(x.parse()?, y.parse()?),
+            [x, "RSHIFT", y] => Gate::RShift(x.parse()?, y.parse()?),
+            _ => return Err(CircuitError::InvalidInstruction(s.to_string())),
         };
+
+        Ok(gate)
+    }
+}
+
+impl fmt::Display for CircuitError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            CircuitError::InvalidInstruction(line) => write!(f, "invalid instruction '{}'", line),
+            CircuitError::DuplicateWire(wire) => write!(f, "wire {} has multiple drivers", wire),
+            CircuitError::UndefinedWires(wires) => {
+                write!(f, "undefined wires: {}", wires.join(", "))
+            }
+            CircuitError::Cycle(wires) => write!(f, "cycle between wires: {}", wires.join(", ")),
+        }
     }
+}
 
-    fn eval_double(&self, x: &str, op: &str, y: &str) -> Option<u16> {
-        let x_val = self.eval_direct(x);
-        let y_val = self.eval_direct(y);
+impl Circuit {
+    pub fn new(gates: HashMap<String, Gate>) -> Result<Circuit, CircuitError> {
+        let mut undefined: Vec<String> = gates
+            .values()
+            .flat_map(|g| g.inputs())
+            .filter(|w| !gates.contains_key(*w))
+            .map(|w| w.to_string())
+            .collect::<HashSet<_>>()
+            .into_iter()
+            .collect();
 
-        if let (Some(x), Some(y)) = (x_val, y_val) {
-            return Some(double_op_bitwise(op, x, y));
+        if !undefined.is_empty() {
+            undefined.sort();
+            return Err(CircuitError::UndefinedWires(undefined));
         }
-        None
+
+        let mut dependents: HashMap<String, Vec<String>> = HashMap::new();
+        for (wire, gate) in &gates {
+            for input in gate.inputs() {
+                dependents
+                    .entry(input.to_string())
+                    .or_default()
+                    .push(wire.to_string());
+            }
+        }
+
+        for wires in dependents.values_mut() {
+            wires.sort();
+        }
+
+        let order = topological_order(&gates, &dependents)?;
+        let position = order
+            .iter()
+            .enumerate()
+            .map(|(i, w)| (w.to_string(), i))
+            .collect();
+
+        Ok(Circuit {
+            gates,
+            order,
+            position,
+            dependents,
+            overrides: HashMap::new(),
+            signals: HashMap::new(),
+        })
     }
 
-    fn eval_single(&self, x: &str, op: &str) -> Option<u16> {
-        let x_val = self.eval_direct(x);
-        x_val.map(|x| single_op_bitwise(op, x))
+    pub fn eval(&mut self, wire: &str) -> Result<u16, CircuitError> {
+        let target = *self
+            .position
+            .get(wire)
+            .ok_or_else(|| CircuitError::UndefinedWires(vec![wire.to_string()]))?;
+
+        for idx in 0..=target {
+            let name = &self.order[idx];
+            if self.signals.contains_key(name) {
+                continue;
+            }
+
+            let value = match self.overrides.get(name) {
+                Some(value) => *value,
+                None => {
+                    let signals = &self.signals;
+                    self.gates[name].eval(|op| match op {
+                        Operand::Signal(s) => *s,
+                        Operand::Wire(w) => signals[w],
+                    })
+                }
+            };
+            self.signals.insert(name.to_string(), value);
+        }
+
+        Ok(self.signals[wire])
+    }
+
+    pub fn override_wire(&mut self, wire: &str, value: u16) -> Result<(), CircuitError> {
+        if !self.gates.contains_key(wire) {
+            return Err(CircuitError::UndefinedWires(vec![wire.to_string()]));
+        }
+
+        self.overrides.insert(wire.to_string(), value);
+        self.invalidate(wire);
+        Ok(())
+    }
+
+    pub fn wire_count(&self) -> usize {
+        self.order.len()
     }
 
-    fn eval_direct(&self, x: &str) -> Option<u16> {
-        return match x.parse::<u16>() {
-            Ok(n) => Some(n),
-            Err(_e) => {
-                return self.lookup.get(x).cloned();
+    fn driver(&self, wire: &str) -> Gate {
+        match self.overrides.get(wire) {
+            Some(value) => Gate::Buffer(Operand::Signal(*value)),
+            None => self.gates[wire].clone(),
+        }
+    }
+
+    pub fn fold_constants(&self, symbolic: &[&str]) -> Result<Circuit, CircuitError> {
+        let mut constants: HashMap<&str, u16> = HashMap::new();
+        let mut gates: HashMap<String, Gate> = HashMap::with_capacity(self.gates.len());
+
+        for wire in &self.order {
+            let gate = self
+                .driver(wire)
+                .map_operands(|op| match op.wire().and_then(|w| constants.get(w)) {
+                    Some(value) => Operand::Signal(*value),
+                    None => op.clone(),
+                })
+                .fold();
+
+            if let Gate::Buffer(Operand::Signal(value)) = gate {
+                if !symbolic.contains(&wire.as_str()) {
+                    constants.insert(wire, value);
+                }
             }
-        };
+            gates.insert(wire.to_string(), gate);
+        }
+
+        Circuit::new(gates)
+    }
+
+    pub fn retain_cone(&self, wire: &str) -> Result<Circuit, CircuitError> {
+        if !self.gates.contains_key(wire) {
+            return Err(CircuitError::UndefinedWires(vec![wire.to_string()]));
+        }
+
+        let mut queue: VecDeque<&str> = VecDeque::from(vec![wire]);
+        let mut live: HashSet<&str> = HashSet::new();
+        while let Some(current) = queue.pop_front() {
+            if live.insert(current) {
+                queue.extend(self.gates[current].inputs());
+            }
+        }
+
+        let gates = live
+            .iter()
+            .map(|w| (w.to_string(), self.gates[*w].clone()))
+            .collect();
+        let mut circuit = Circuit::new(gates)?;
+        circuit.overrides = self
+            .overrides
+            .iter()
+            .filter(|(w, _)| live.contains(w.as_str()))
+            .map(|(w, v)| (w.to_string(), *v))
+            .collect();
+
+        Ok(circuit)
+    }
+
+    pub fn simplify(&self, wire: &str, symbolic: &[&str]) -> Result<Circuit, CircuitError> {
+        self.fold_constants(symbolic)?.retain_cone(wire)
+    }
+
+    pub fn to_dot(&self) -> String {
+        let mut dot = "digraph circuit {\n".to_string();
+
+        for wire in &self.order {
+            let gate = self.driver(wire);
+            dot += &format!("    \"{}\" [label=\"{}\\n{}\"];\n", wire, wire, gate);
+            for input in gate.inputs() {
+                dot += &format!("    \"{}\" -> \"{}\";\n", input, wire);
+            }
+        }
+
+        dot += "}\n";
+        dot
+    }
+
+    fn invalidate(&mut self, wire: &str) {
+        let mut queue: VecDeque<&str> = VecDeque::from(vec![wire]);
+        let mut visited: HashSet<&str> = HashSet::new();
+
+        while let Some(current) = queue.pop_front() {
+            if !visited.insert(current) {
+                continue;
+            }
+            self.signals.remove(current);
+            if let Some(next) = self.dependents.get(current) {
+                queue.extend(next.iter().map(|w| w.as_str()));
+            }
+        }
+    }
+}
+
+fn topological_order(
+    gates: &HashMap<String, Gate>,
+    dependents: &HashMap<String, Vec<String>>,
+) -> Result<Vec<String>, CircuitError> {
+    let mut in_degree: HashMap<&str, usize> = gates
+        .iter()
+        .map(|(wire, gate)| (wire.as_str(), gate.inputs().count()))
+        .collect();
+
+    let mut ready: Vec<&str> = in_degree
+        .iter()
+        .filter(|(_, degree)| **degree == 0)
+        .map(|(wire, _)| *wire)
+        .collect();
+    ready.sort_unstable();
+    let mut queue: VecDeque<&str> = ready.into_iter().collect();
+    let mut order: Vec<String> = Vec::with_capacity(gates.len());
+
+    while let Some(wire) = queue.pop_front() {
+        order.push(wire.to_string());
+        for dependent in dependents.get(wire).into_iter().flatten() {
+            let degree = in_degree.get_mut(dependent.as_str()).unwrap();
+            *degree -= 1;
+            if *degree == 0 {
+                queue.push_back(dependent);
+            }
+        }
+    }
+
+    if order.len() < gates.len() {
+        let blocked: HashSet<&str> = in_degree
+            .into_iter()
+            .filter(|(_, degree)| *degree > 0)
+            .map(|(wire, _)| wire)
+            .collect();
+        return Err(CircuitError::Cycle(cyclic_wires(blocked, dependents)));
+    }
+
+    Ok(order)
+}
+
+fn cyclic_wires(
+    mut blocked: HashSet<&str>,
+    dependents: &HashMap<String, Vec<String>>,
+) -> Vec<String> {
+    // Wires left over by Kahn's algorithm include everything downstream of a cycle,
+    // peel those off until only wires feeding back into the set remain.
+    loop {
+        let downstream: Vec<&str> = blocked
+            .iter()
+            .filter(|wire| {
+                !dependents
+                    .get(**wire)
+                    .into_iter()
+                    .flatten()
+                    .any(|d| blocked.contains(d.as_str()))
+            })
+            .cloned()
+            .collect();
+
+        if downstream.is_empty() {
+            break;
+        }
+        for wire in downstream {
+            blocked.remove(wire);
+        }
+    }
+
+    let mut cyclic: Vec<String> = blocked.into_iter().map(|w| w.to_string()).collect();
+    cyclic.sort();
+    cyclic
+}
+
+pub fn parse_instruction(line: &str) -> Result<(String, Gate), CircuitError> {
+    let (expr, output) = line
+        .split_once("->")
+        .ok_or_else(|| CircuitError::InvalidInstruction(line.to_string()))?;
+
+    match output.trim().parse()? {
+        Operand::Wire(wire) => Ok((wire, expr.trim().parse()?)),
+        Operand::Signal(_) => Err(CircuitError::InvalidInstruction(line.to_string())),
     }
 }
 
-pub fn double_op_bitwise(op: &str, op1: u16, op2: u16) -> u16 {
-    match op {
-        "AND" => op1 & op2,
-        "OR" => op1 | op2,
-        "RSHIFT" => op1 >> op2,
-        "LSHIFT" => op1 << op2,
-        _ => panic!("Invalid operation: {}", op),
+pub fn parse_circuit(input: &[String]) -> Result<Circuit, CircuitError> {
+    let mut gates: HashMap<String, Gate> = HashMap::new();
+
+    for line in input.iter().filter(|l| !l.trim().is_empty()) {
+        let (wire, gate) = parse_instruction(line)?;
+        if gates.insert(wire.to_string(), gate).is_some() {
+            return Err(CircuitError::DuplicateWire(wire));
+        }
     }
+
+    Circuit::new(gates)
 }
 
-pub fn single_op_bitwise(op: &str, op1: u16) -> u16 {
-    match op {
-        "NOT" => !op1,
-        _ => panic!("Invalid operation: {}", op),
+#[cfg(test)]
+mod tests {
+    use crate::aoc_2015::day07::{parse_circuit, parse_instruction, CircuitError, Gate, Operand};
+    use crate::common::parse::test_utils::vec_of_strings;
+
+    fn example() -> Vec<String> {
+        vec_of_strings![
+            "123 -> x",
+            "456 -> y",
+            "x AND y -> d",
+            "x OR y -> e",
+            "x LSHIFT 2 -> f",
+            "y RSHIFT 2 -> g",
+            "NOT x -> h",
+            "NOT y -> i"
+        ]
+    }
+
+    #[test]
+    fn test_parse_instruction() {
+        // given
+        let line = "x LSHIFT 2 -> f";
+
+        // when
+        let result = parse_instruction(line);
+
+        // then
+        let gate = Gate::LShift(Operand::Wire("x".to_string()), Operand::Signal(2));
+        assert_eq!(result, Ok(("f".to_string(), gate)));
+    }
+
+    #[test]
+    fn test_parse_invalid_instruction() {
+        let result = parse_instruction("x XOR y -> z");
+        assert_eq!(
+            result,
+            Err(CircuitError::InvalidInstruction("x XOR y".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_eval_example() {
+        // given
+        let mut circuit = parse_circuit(&example()).unwrap();
+
+        // when
+        let signals: Vec<u16> = ["d", "e", "f", "g", "h", "i", "x", "y"]
+            .iter()
+            .map(|w| circuit.eval(w).unwrap())
+            .collect();
+
+        // then
+        assert_eq!(signals, vec![72, 507, 492, 114, 65412, 65079, 123, 456]);
+    }
+
+    #[test]
+    fn test_override_reevaluates_dependents() {
+        // given
+        let mut circuit = parse_circuit(&example()).unwrap();
+        assert_eq!(circuit.eval("d").unwrap(), 72);
+
+        // when
+        circuit.override_wire("x", 0xFFFF).unwrap();
+
+        // then
+        assert_eq!(circuit.eval("d").unwrap(), 456);
+        assert_eq!(circuit.eval("h").unwrap(), 0);
+        assert_eq!(circuit.eval("y").unwrap(), 456);
+    }
+
+    #[test]
+    fn test_fold_constants() {
+        // given
+        let input = vec_of_strings![
+            "3 -> x",
+            "x LSHIFT 2 -> y",
+            "in AND y -> z",
+            "z OR 0 -> out",
+            "1 -> in"
+        ];
+        let circuit = parse_circuit(&input).unwrap();
+
+        // when
+        let folded = circuit.fold_constants(&["in"]).unwrap();
+
+        // then
+        let expected = vec_of_strings![
+            "digraph circuit {",
+            "    \"in\" [label=\"in\\n1\"];",
+            "    \"x\" [label=\"x\\n3\"];",
+            "    \"y\" [label=\"y\\n12\"];",
+            "    \"z\" [label=\"z\\nin AND 12\"];",
+            "    \"in\" -> \"z\";",
+            "    \"out\" [label=\"out\\nz\"];",
+            "    \"z\" -> \"out\";",
+            "}",
+            ""
+        ];
+        assert_eq!(folded.to_dot(), expected.join("\n"));
+    }
+
+    #[test]
+    fn test_simplify_drops_dead_wires() {
+        // given
+        let mut circuit = parse_circuit(&example()).unwrap();
+
+        // when
+        let mut simplified = circuit.simplify("d", &["x"]).unwrap();
+
+        // then
+        let expected = vec_of_strings![
+            "digraph circuit {",
+            "    \"x\" [label=\"x\\n123\"];",
+            "    \"d\" [label=\"d\\nx AND 456\"];",
+            "    \"x\" -> \"d\";",
+            "}",
+            ""
+        ];
+        assert_eq!(simplified.wire_count(), 2);
+        assert_eq!(simplified.to_dot(), expected.join("\n"));
+        assert_eq!(circuit.eval("d"), simplified.eval("d"));
+    }
+
+    #[test]
+    fn test_reports_cycle() {
+        let input = vec_of_strings!["1 -> a", "a AND c -> b", "b -> c", "c -> d"];
+        let result = parse_circuit(&input);
+        assert!(matches!(result, Err(CircuitError::Cycle(wires)) if wires == vec!["b", "c"]));
+    }
+
+    #[test]
+    fn test_reports_undefined_wires() {
+        let input = vec_of_strings!["1 -> a", "a AND q -> b", "NOT z -> c"];
+        let result = parse_circuit(&input);
+        assert!(
+            matches!(result, Err(CircuitError::UndefinedWires(wires)) if wires == vec!["q", "z"])
+        );
     }
 }
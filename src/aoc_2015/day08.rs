@@ -1,26 +1,136 @@
-use onig::Regex;
-use std::str;
+use std::fmt;
+use std::fmt::Formatter;
 
 pub fn run(input: &str) {
     println!(
         "Number of extra characters: {}",
         extra_chars_unescaped(input)
     );
+    println!(
+        "Number of extra encoded characters: {}",
+        extra_chars_escaped(input)
+    );
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EscapeErrorKind {
+    NotQuoted,
+    UnescapedQuote,
+    UnknownEscape(char),
+    InvalidHex,
+    UnexpectedEnd,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EscapeError {
+    pub position: usize,
+    pub kind: EscapeErrorKind,
+}
+
+impl fmt::Display for EscapeError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self.kind {
+            EscapeErrorKind::NotQuoted => write!(f, "literal is not quoted"),
+            EscapeErrorKind::UnescapedQuote => {
+                write!(f, "unescaped quote at position {}", self.position)
+            }
+            EscapeErrorKind::UnknownEscape(c) => {
+                write!(f, "unknown escape '\\{}' at position {}", c, self.position)
+            }
+            EscapeErrorKind::InvalidHex => {
+                write!(f, "invalid hex escape at position {}", self.position)
+            }
+            EscapeErrorKind::UnexpectedEnd => {
+                write!(f, "unexpected end of escape at position {}", self.position)
+            }
+        }
+    }
+}
+
+// positions in errors are byte offsets into the quoted literal
+pub fn decode(literal: &str) -> Result<Vec<u8>, EscapeError> {
+    let bytes = literal.as_bytes();
+    if bytes.len() < 2 || bytes[0] != b'"' || bytes[bytes.len() - 1] != b'"' {
+        return Err(EscapeError {
+            position: 0,
+            kind: EscapeErrorKind::NotQuoted,
+        });
+    }
+
+    let end = bytes.len() - 1;
+    let mut decoded = Vec::with_capacity(end);
+    let mut pos = 1;
+    while pos < end {
+        match bytes[pos] {
+            b'"' => {
+                return Err(EscapeError {
+                    position: pos,
+                    kind: EscapeErrorKind::UnescapedQuote,
+                })
+            }
+            b'\\' => {
+                let (byte, len) = decode_escape(&literal[..end], pos)?;
+                decoded.push(byte);
+                pos += len;
+            }
+            byte => {
+                decoded.push(byte);
+                pos += 1;
+            }
+        }
+    }
+    Ok(decoded)
+}
+
+fn decode_escape(body: &str, start: usize) -> Result<(u8, usize), EscapeError> {
+    let bytes = body.as_bytes();
+    let error = |kind| EscapeError {
+        position: start,
+        kind,
+    };
+    match bytes.get(start + 1) {
+        None => Err(error(EscapeErrorKind::UnexpectedEnd)),
+        Some(b'\\') => Ok((b'\\', 2)),
+        Some(b'"') => Ok((b'"', 2)),
+        Some(b'x') => {
+            let digits = bytes
+                .get(start + 2..start + 4)
+                .ok_or_else(|| error(EscapeErrorKind::UnexpectedEnd))?;
+            let high = hex_value(digits[0]).ok_or_else(|| error(EscapeErrorKind::InvalidHex))?;
+            let low = hex_value(digits[1]).ok_or_else(|| error(EscapeErrorKind::InvalidHex))?;
+            Ok((high * 16 + low, 4))
+        }
+        Some(_) => {
+            let c = body[start + 1..].chars().next().unwrap();
+            Err(error(EscapeErrorKind::UnknownEscape(c)))
+        }
+    }
+}
+
+fn hex_value(digit: u8) -> Option<u8> {
+    (digit as char).to_digit(16).map(|d| d as u8)
+}
+
+pub fn encode(bytes: &[u8]) -> String {
+    let mut encoded = String::with_capacity(bytes.len() + 2);
+    encoded.push('"');
+    for &byte in bytes {
+        match byte {
+            b'"' => encoded.push_str("\\\""),
+            b'\\' => encoded.push_str("\\\\"),
+            b' '..=b'~' => encoded.push(byte as char),
+            _ => encoded.push_str(&format!("\\x{:02x}", byte)),
+        }
+    }
+    encoded.push('"');
+    encoded
 }
 
 pub fn raw_and_unescaped_len(s: &str) -> (usize, usize) {
-    if !s.starts_with('"') || !s.ends_with('"') {
-        panic!("invalid format (not quoted)");
+    match decode(s) {
+        Ok(decoded) => (s.len(), decoded.len()),
+        Err(e) => panic!("Invalid literal {}: {}", s, e),
     }
-    let raw_len = s.len();
-    let re = Regex::new(r#"\\(\\|"|x[0-9a-f]{2})"#).unwrap();
-    let ss = &s[1..s.len() - 1];
-    let (esc_count, esc_size) =
-        re.find_iter(ss)
-            .fold((0, 0), |(esc_count, esc_size), (start_pos, end_pos)| {
-                (esc_count + 1, esc_size + (end_pos - start_pos))
-            });
-    (raw_len, raw_len - 2 - esc_size + esc_count)
 }
 
 pub fn extra_chars_unescaped(text: &str) -> usize {
@@ -29,3 +139,99 @@ pub fn extra_chars_unescaped(text: &str) -> usize {
         extra_chars + (raw_len - unescaped_len)
     })
 }
+
+// part 2 only escapes quotes and backslashes, this matches `encode` as long as every line is
+// printable ASCII, which holds for the raw literals in the puzzle input
+pub fn extra_chars_escaped(text: &str) -> usize {
+    text.lines()
+        .map(|line| encode(line.as_bytes()).len() - line.len())
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode() {
+        let cases = [
+            (r#""""#, vec![]),
+            (r#""abc""#, b"abc".to_vec()),
+            (r#""aaa\"aaa""#, b"aaa\"aaa".to_vec()),
+            (r#""\x27\\\xFF""#, vec![0x27, b'\\', 0xff]),
+        ];
+
+        for (input, expected) in cases {
+            assert_eq!(decode(input), Ok(expected), "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_decode_errors() {
+        let cases = [
+            ("abc", 0, EscapeErrorKind::NotQuoted),
+            ("\"", 0, EscapeErrorKind::NotQuoted),
+            (r#""a"b""#, 2, EscapeErrorKind::UnescapedQuote),
+            (r#""ab\n""#, 3, EscapeErrorKind::UnknownEscape('n')),
+            (r#""\xg1""#, 1, EscapeErrorKind::InvalidHex),
+            (r#""ab\x1""#, 3, EscapeErrorKind::UnexpectedEnd),
+            (r#""ab\""#, 3, EscapeErrorKind::UnexpectedEnd),
+        ];
+
+        for (input, position, kind) in cases {
+            assert_eq!(
+                decode(input),
+                Err(EscapeError { position, kind }),
+                "{}",
+                input
+            );
+        }
+    }
+
+    #[test]
+    fn test_extra_chars() {
+        // given
+        let input = "\"\"\n\"abc\"\n\"aaa\\\"aaa\"\n\"\\x27\"";
+
+        // when
+        let unescaped = extra_chars_unescaped(input);
+        let escaped = extra_chars_escaped(input);
+
+        // then
+        assert_eq!(unescaped, 12);
+        assert_eq!(escaped, 19);
+    }
+
+    struct XorShift(u64);
+
+    impl XorShift {
+        fn next(&mut self) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0
+        }
+    }
+
+    #[test]
+    fn test_encode_decode_round_trip() {
+        let mut rng = XorShift(0x2015_0008);
+        for _ in 0..1000 {
+            // given
+            let len = (rng.next() % 32) as usize;
+            let bytes: Vec<u8> = (0..len)
+                .map(|_| match rng.next() % 4 {
+                    0 => b"\\\"x"[(rng.next() % 3) as usize],
+                    _ => rng.next() as u8,
+                })
+                .collect();
+
+            // when
+            let encoded = encode(&bytes);
+
+            // then
+            assert!(encoded.bytes().all(|b| (b' '..=b'~').contains(&b)));
+            assert_eq!(decode(&encoded), Ok(bytes), "{}", encoded);
+        }
+    }
+}
@@ -1,41 +1,427 @@
+use lazy_static::lazy_static;
+use num::{BigUint, Zero};
+use std::collections::HashMap;
+
 pub fn run(input: &str) {
-    let mut seq = input.to_string();
-    for _ in 0..50 {
-        let new_seq = look_and_say(&seq);
-        seq = new_seq;
+    let mut seq = to_digits(input);
+    for step in 1..=50 {
+        seq = look_and_say_bytes(&seq);
+        if step == 40 {
+            println!("seq len after 40: {}", seq.len());
+        }
     }
-    println!("seq len: {}", seq.len())
-}
+    println!("seq len: {}", seq.len());
 
-#[derive(Debug)]
-struct Group {
-    symbol: char,
-    count: usize,
+    let steps = 500;
+    match ElementCounts::decompose(input) {
+        Some(counts) => println!(
+            "seq len after {}: {}",
+            steps,
+            counts.advance(steps).length()
+        ),
+        None => println!("{} is not a compound of common elements", input),
+    }
+    println!("seq len after 50 via elements: {}", length_after(input, 50));
 }
 
-pub fn look_and_say(input: &str) -> String {
-    let mut prev = input.chars().next().unwrap();
-    let mut count = 1;
-    let mut groups: Vec<Group> = vec![];
+// Conway's common elements: name, sequence and the elements it decays into after one step
+const ELEMENTS: [(&str, &str, &[&str]); 92] = [
+    ("H", "22", &["H"]),
+    (
+        "He",
+        "13112221133211322112211213322112",
+        &["Hf", "Pa", "H", "Ca", "Li"],
+    ),
+    ("Li", "312211322212221121123222112", &["He"]),
+    (
+        "Be",
+        "111312211312113221133211322112211213322112",
+        &["Ge", "Ca", "Li"],
+    ),
+    ("B", "1321132122211322212221121123222112", &["Be"]),
+    ("C", "3113112211322112211213322112", &["B"]),
+    ("N", "111312212221121123222112", &["C"]),
+    ("O", "132112211213322112", &["N"]),
+    ("F", "31121123222112", &["O"]),
+    ("Ne", "111213322112", &["F"]),
+    ("Na", "123222112", &["Ne"]),
+    ("Mg", "3113322112", &["Pm", "Na"]),
+    ("Al", "1113222112", &["Mg"]),
+    ("Si", "1322112", &["Al"]),
+    ("P", "311311222112", &["Ho", "Si"]),
+    ("S", "1113122112", &["P"]),
+    ("Cl", "132112", &["S"]),
+    ("Ar", "3112", &["Cl"]),
+    ("K", "1112", &["Ar"]),
+    ("Ca", "12", &["K"]),
+    ("Sc", "3113112221133112", &["Ho", "Pa", "H", "Ca", "Co"]),
+    ("Ti", "11131221131112", &["Sc"]),
+    ("V", "13211312", &["Ti"]),
+    ("Cr", "31132", &["V"]),
+    ("Mn", "111311222112", &["Cr", "Si"]),
+    ("Fe", "13122112", &["Mn"]),
+    ("Co", "32112", &["Fe"]),
+    ("Ni", "11133112", &["Zn", "Co"]),
+    ("Cu", "131112", &["Ni"]),
+    ("Zn", "312", &["Cu"]),
+    (
+        "Ga",
+        "13221133122211332",
+        &["Eu", "Ca", "Ac", "H", "Ca", "Zn"],
+    ),
+    ("Ge", "31131122211311122113222", &["Ho", "Ga"]),
+    ("As", "11131221131211322113322112", &["Ge", "Na"]),
+    ("Se", "13211321222113222112", &["As"]),
+    ("Br", "3113112211322112", &["Se"]),
+    ("Kr", "11131221222112", &["Br"]),
+    ("Rb", "1321122112", &["Kr"]),
+    ("Sr", "3112112", &["Rb"]),
+    ("Y", "1112133", &["Sr", "U"]),
+    ("Zr", "12322211331222113112211", &["Y", "H", "Ca", "Tc"]),
+    ("Nb", "1113122113322113111221131221", &["Er", "Zr"]),
+    ("Mo", "13211322211312113211", &["Nb"]),
+    ("Tc", "311322113212221", &["Mo"]),
+    ("Ru", "132211331222113112211", &["Eu", "Ca", "Tc"]),
+    ("Rh", "311311222113111221131221", &["Ho", "Ru"]),
+    ("Pd", "111312211312113211", &["Rh"]),
+    ("Ag", "132113212221", &["Pd"]),
+    ("Cd", "3113112211", &["Ag"]),
+    ("In", "11131221", &["Cd"]),
+    ("Sn", "13211", &["In"]),
+    ("Sb", "3112221", &["Pm", "Sn"]),
+    ("Te", "1322113312211", &["Eu", "Ca", "Sb"]),
+    ("I", "311311222113111221", &["Ho", "Te"]),
+    ("Xe", "11131221131211", &["I"]),
+    ("Cs", "13211321", &["Xe"]),
+    ("Ba", "311311", &["Cs"]),
+    ("La", "11131", &["Ba"]),
+    ("Ce", "1321133112", &["La", "H", "Ca", "Co"]),
+    ("Pr", "31131112", &["Ce"]),
+    ("Nd", "111312", &["Pr"]),
+    ("Pm", "132", &["Nd"]),
+    ("Sm", "311332", &["Pm", "Ca", "Zn"]),
+    ("Eu", "1113222", &["Sm"]),
+    ("Gd", "13221133112", &["Eu", "Ca", "Co"]),
+    ("Tb", "3113112221131112", &["Ho", "Gd"]),
+    ("Dy", "111312211312", &["Tb"]),
+    ("Ho", "1321132", &["Dy"]),
+    ("Er", "311311222", &["Ho", "Pm"]),
+    ("Tm", "11131221133112", &["Er", "Ca", "Co"]),
+    ("Yb", "1321131112", &["Tm"]),
+    ("Lu", "311312", &["Yb"]),
+    ("Hf", "11132", &["Lu"]),
+    (
+        "Ta",
+        "13112221133211322112211213322113",
+        &["Hf", "Pa", "H", "Ca", "W"],
+    ),
+    ("W", "312211322212221121123222113", &["Ta"]),
+    (
+        "Re",
+        "111312211312113221133211322112211213322113",
+        &["Ge", "Ca", "W"],
+    ),
+    ("Os", "1321132122211322212221121123222113", &["Re"]),
+    ("Ir", "3113112211322112211213322113", &["Os"]),
+    ("Pt", "111312212221121123222113", &["Ir"]),
+    ("Au", "132112211213322113", &["Pt"]),
+    ("Hg", "31121123222113", &["Au"]),
+    ("Tl", "111213322113", &["Hg"]),
+    ("Pb", "123222113", &["Tl"]),
+    ("Bi", "3113322113", &["Pm", "Pb"]),
+    ("Po", "1113222113", &["Bi"]),
+    ("At", "1322113", &["Po"]),
+    ("Rn", "311311222113", &["Ho", "At"]),
+    ("Fr", "1113122113", &["Rn"]),
+    ("Ra", "132113", &["Fr"]),
+    ("Ac", "3113", &["Ra"]),
+    ("Th", "1113", &["Ac"]),
+    ("Pa", "13", &["Th"]),
+    ("U", "3", &["Pa"]),
+];
+
+lazy_static! {
+    static ref SEQUENCES: Vec<Vec<u8>> = ELEMENTS.iter().map(|(_, s, _)| to_digits(s)).collect();
+    static ref DECAYS: Vec<Vec<usize>> = decay_table();
+    static ref ELEMENT_IDS: HashMap<Vec<u8>, usize> = SEQUENCES
+        .iter()
+        .enumerate()
+        .map(|(id, sequence)| (sequence.clone(), id))
+        .collect();
+}
 
-    for c in input.chars().skip(1) {
-        if c != prev {
-            let symbol = prev;
-            let group = Group { symbol, count };
-            groups.push(group);
-            count = 1;
-        } else {
+pub fn look_and_say_bytes(input: &[u8]) -> Vec<u8> {
+    let mut result = Vec::with_capacity(input.len() * 2);
+    let mut i = 0;
+    while i < input.len() {
+        let symbol = input[i];
+        let mut count = 1;
+        while i + count < input.len() && input[i + count] == symbol {
             count += 1;
         }
-        prev = c
+        push_count(&mut result, count);
+        result.push(symbol);
+        i += count;
+    }
+    result
+}
+
+fn push_count(result: &mut Vec<u8>, count: usize) {
+    if count >= 10 {
+        push_count(result, count / 10);
+    }
+    result.push((count % 10) as u8);
+}
+
+fn to_digits(input: &str) -> Vec<u8> {
+    input
+        .trim()
+        .chars()
+        .map(|c| {
+            c.to_digit(10)
+                .unwrap_or_else(|| panic!("Invalid look-and-say digit {}", c)) as u8
+        })
+        .collect()
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ElementCounts {
+    counts: Vec<BigUint>,
+}
+
+impl ElementCounts {
+    // succeeds only for compounds of common elements, the input should be at least two steps old
+    pub fn decompose(input: &str) -> Option<ElementCounts> {
+        decompose_digits(&to_digits(input))
+    }
+
+    pub fn advance(&self, steps: usize) -> ElementCounts {
+        let mut counts = self.counts.clone();
+        for _ in 0..steps {
+            let mut next = vec![BigUint::zero(); ELEMENTS.len()];
+            for (element, count) in counts.iter().enumerate() {
+                if count.is_zero() {
+                    continue;
+                }
+                for &product in &DECAYS[element] {
+                    next[product] += count;
+                }
+            }
+            counts = next;
+        }
+        ElementCounts { counts }
     }
 
-    let symbol = prev;
-    let last = Group { count, symbol };
-    groups.push(last);
-    groups.iter().map(transform_group).collect()
+    pub fn length(&self) -> BigUint {
+        self.counts
+            .iter()
+            .zip(ELEMENTS.iter())
+            .map(|(count, (_, sequence, _))| count * BigUint::from(sequence.len()))
+            .sum()
+    }
 }
 
-fn transform_group(group: &Group) -> String {
-    format!("{}{}", group.count, group.symbol)
+// simulates the digits until they decompose into elements, then only tracks element counts
+pub fn length_after(input: &str, steps: usize) -> BigUint {
+    let mut seq = to_digits(input);
+    for step in 0..steps {
+        // a digit above 3 is never consumed and no element contains one
+        if step >= 2 && seq.iter().all(|&d| d <= 3) {
+            if let Some(counts) = decompose_digits(&seq) {
+                return counts.advance(steps - step).length();
+            }
+        }
+        seq = look_and_say_bytes(&seq);
+    }
+    BigUint::from(seq.len())
+}
+
+fn decompose_digits(digits: &[u8]) -> Option<ElementCounts> {
+    if digits.is_empty() {
+        return None;
+    }
+
+    let split_points = (1..digits.len()).filter(|&at| splits_at(digits, at));
+    let mut counts = vec![BigUint::zero(); ELEMENTS.len()];
+    let mut start = 0;
+    for end in split_points.chain(std::iter::once(digits.len())) {
+        let element = ELEMENT_IDS.get(&digits[start..end])?;
+        counts[*element] += 1u32;
+        start = end;
+    }
+    Some(ElementCounts { counts })
+}
+
+// Conway's splitting theorem, holds for sequences that are at least two steps old
+fn splits_at(digits: &[u8], at: usize) -> bool {
+    let right = &digits[at..];
+    match digits[at - 1] {
+        left if left >= 4 => right[0] <= 3,
+        2 => splits_before(right),
+        _ => right[0] == 2 && run_length(right) == 2 && splits_before(&right[2..]),
+    }
+}
+
+// true when `right` starts with 1^1 X^1, 1^3, 3^1 X^!3, 3^2 X^!3, n^1 with n >= 4, or is empty
+fn splits_before(right: &[u8]) -> bool {
+    if right.is_empty() {
+        return true;
+    }
+    let run = run_length(right);
+    match (right[0], run) {
+        (1, 1) => run_length(&right[1..]) == 1,
+        (1, 3) => true,
+        (3, 1) | (3, 2) => run_length(&right[run..]) != 3,
+        (digit, 1) => digit >= 4,
+        _ => false,
+    }
+}
+
+fn run_length(digits: &[u8]) -> usize {
+    digits.iter().take_while(|&&d| d == digits[0]).count()
+}
+
+fn decay_table() -> Vec<Vec<usize>> {
+    let ids: HashMap<&str, usize> = ELEMENTS
+        .iter()
+        .enumerate()
+        .map(|(id, (name, _, _))| (*name, id))
+        .collect();
+
+    ELEMENTS
+        .iter()
+        .map(|(_, _, decays)| decays.iter().map(|name| ids[name]).collect())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn look_and_say(input: &str) -> String {
+        look_and_say_bytes(&to_digits(input))
+            .iter()
+            .map(|d| (b'0' + d) as char)
+            .collect()
+    }
+
+    #[test]
+    fn test_look_and_say() {
+        let cases = [
+            ("1", "11"),
+            ("11", "21"),
+            ("21", "1211"),
+            ("1211", "111221"),
+            ("111221", "312211"),
+            ("1111111111", "101"),
+        ];
+
+        for (input, expected) in cases {
+            assert_eq!(look_and_say(input), expected);
+        }
+    }
+
+    #[test]
+    fn test_decay_table_is_consistent() {
+        let decays = &*DECAYS;
+        for (element, (name, sequence, _)) in ELEMENTS.iter().enumerate() {
+            let products: String = decays[element]
+                .iter()
+                .map(|&product| ELEMENTS[product].1)
+                .collect();
+            assert_eq!(look_and_say(sequence), products, "{}", name);
+        }
+    }
+
+    #[test]
+    fn test_element_lengths_match_simulation() {
+        // given
+        let input = "1113222113";
+        let mut seq = to_digits(input);
+
+        // when
+        let counts = ElementCounts::decompose(input).unwrap();
+
+        // then
+        for steps in 1..=30 {
+            seq = look_and_say_bytes(&seq);
+            assert_eq!(counts.advance(steps).length(), BigUint::from(seq.len()));
+        }
+    }
+
+    #[test]
+    fn test_length_after_non_element_input() {
+        // given
+        let input = "1";
+        let mut seq = to_digits(input);
+        for _ in 0..40 {
+            seq = look_and_say_bytes(&seq);
+        }
+
+        // then
+        assert!(ElementCounts::decompose(input).is_none());
+        assert_eq!(ElementCounts::decompose("2222"), None);
+        assert_eq!(length_after(input, 40), BigUint::from(seq.len()));
+    }
+
+    fn element(name: &str) -> usize {
+        ELEMENTS.iter().position(|(n, _, _)| *n == name).unwrap()
+    }
+
+    #[test]
+    fn test_decompose_at_split_points() {
+        // given
+        let (h, zn) = (element("H"), element("Zn"));
+
+        // when
+        let counts = ElementCounts::decompose("22312").unwrap();
+
+        // then
+        let mut expected = vec![BigUint::zero(); ELEMENTS.len()];
+        expected[h] += 1u32;
+        expected[zn] += 1u32;
+        assert_eq!(counts.counts, expected);
+        assert_eq!(ElementCounts::decompose("1312"), None);
+    }
+
+    #[test]
+    fn test_elements_do_not_split() {
+        for (name, sequence, _) in ELEMENTS.iter() {
+            let digits = to_digits(sequence);
+            assert!(
+                (1..digits.len()).all(|at| !splits_at(&digits, at)),
+                "{}",
+                name
+            );
+        }
+    }
+
+    #[test]
+    fn test_compound_matches_simulation() {
+        // given
+        let input = "22312";
+        let mut seq = to_digits(input);
+
+        // when
+        let counts = ElementCounts::decompose(input).unwrap();
+
+        // then
+        for steps in 1..=30 {
+            seq = look_and_say_bytes(&seq);
+            assert_eq!(counts.advance(steps).length(), BigUint::from(seq.len()));
+        }
+    }
+
+    #[test]
+    fn test_length_after_transuranic_input() {
+        // given
+        let input = "14";
+        let mut seq = to_digits(input);
+        for _ in 0..20 {
+            seq = look_and_say_bytes(&seq);
+        }
+
+        // then
+        assert_eq!(length_after(input, 20), BigUint::from(seq.len()));
+    }
 }
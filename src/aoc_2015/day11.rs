@@ -1,259 +1,441 @@
-use itertools::Itertools;
+const PASSWORD_LEN: usize = 8;
+const ALPHABET_LEN: u8 = 26;
 
 pub fn run(input: &str) {
     println!("input: {}", input);
 
-    let mut current = input.to_string();
-    let mut iter = 1;
+    let rules = PasswordRules::default();
+    let next = next_passwords(input, 2, &rules);
+    println!("Next password: {}", next[0]);
+    println!("Password after that: {}", next[1]);
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PasswordRules {
+    pub forbidden: Vec<u8>,
+    pub straight_len: usize,
+    pub pair_count: usize,
+}
 
-    loop {
-        let new_pwd = increment_password(current.to_string());
-        current = new_pwd.clone();
-        println!("Iter: {}. Checking: {}", iter, current);
-        iter += 1;
-        if let true = valid_password(current.as_str()) {
-            break;
+impl Default for PasswordRules {
+    fn default() -> Self {
+        PasswordRules {
+            forbidden: b"iol".to_vec(),
+            straight_len: 3,
+            pair_count: 2,
         }
     }
 }
 
-fn next_in_alphabet(c: char) -> char {
-    match c {
-        'z' => 'a',
-        _ => std::char::from_u32(c as u32 + 1).unwrap_or(c),
+impl PasswordRules {
+    fn is_allowed(&self, letter: u8) -> bool {
+        !self.forbidden.contains(&letter)
+    }
+
+    fn has_no_forbidden(&self, password: &[u8]) -> bool {
+        password.iter().all(|&c| self.is_allowed(c))
+    }
+
+    fn is_valid(&self, password: &[u8]) -> bool {
+        self.has_no_forbidden(password) && self.has_straight(password) && self.has_pairs(password)
+    }
+
+    fn has_straight(&self, password: &[u8]) -> bool {
+        if self.straight_len <= 1 {
+            return true;
+        }
+        password
+            .windows(self.straight_len)
+            .any(|w| w.windows(2).all(|p| p[0] + 1 == p[1]))
+    }
+
+    fn has_pairs(&self, password: &[u8]) -> bool {
+        let mut letters: Vec<u8> = password
+            .windows(2)
+            .filter(|p| p[0] == p[1])
+            .map(|p| p[0])
+            .collect();
+        letters.sort_unstable();
+        letters.dedup();
+        letters.len() >= self.pair_count
     }
 }
 
-fn increment_password(input: String) -> String {
-    let mut res: String = String::from("");
+pub struct Passwords<'a> {
+    current: Option<[u8; PASSWORD_LEN]>,
+    rules: &'a PasswordRules,
+}
+
+impl<'a> Passwords<'a> {
+    pub fn after(input: &str, rules: &'a PasswordRules) -> Passwords<'a> {
+        Passwords {
+            current: Some(parse_password(input)),
+            rules,
+        }
+    }
 
-    let mut keep_adding = true;
-    for c in input.chars().rev() {
-        if keep_adding {
-            let next = next_in_alphabet(c);
-            res.push(next);
-            if next != 'a' {
-                keep_adding = false
+    // smallest password >= candidate that has a straight and no forbidden letters
+    fn next_with_straight(&self, candidate: &[u8; PASSWORD_LEN]) -> Option<[u8; PASSWORD_LEN]> {
+        let len = self.rules.straight_len;
+        if self.rules.has_no_forbidden(candidate) && self.rules.has_straight(candidate) {
+            return Some(*candidate);
+        }
+        if len <= 1 {
+            return self.next_matching(candidate, &[None; PASSWORD_LEN]);
+        }
+        if len > PASSWORD_LEN || len > ALPHABET_LEN as usize {
+            return None;
+        }
+
+        let mut best: Option<[u8; PASSWORD_LEN]> = None;
+        for start in 0..=PASSWORD_LEN - len {
+            for first in b'a'..=b'z' - (len as u8 - 1) {
+                let mut fixed = [None; PASSWORD_LEN];
+                for (offset, slot) in fixed[start..start + len].iter_mut().enumerate() {
+                    *slot = Some(first + offset as u8);
+                }
+                if let Some(found) = self.next_matching(candidate, &fixed) {
+                    if !matches!(best, Some(b) if b <= found) {
+                        best = Some(found);
+                    }
+                }
             }
-        } else {
-            res.push(c)
         }
+        best
     }
 
-    res.chars().rev().collect::<String>()
-}
+    // smallest password >= candidate with allowed letters and the given fixed positions
+    fn next_matching(
+        &self,
+        candidate: &[u8; PASSWORD_LEN],
+        fixed: &[Option<u8>; PASSWORD_LEN],
+    ) -> Option<[u8; PASSWORD_LEN]> {
+        let fits =
+            |i: usize, c: u8| self.rules.is_allowed(c) && !matches!(fixed[i], Some(f) if f != c);
+        let smallest = |i: usize| (b'a'..=b'z').find(|&c| fits(i, c));
+
+        let prefix_fits = (0..=PASSWORD_LEN)
+            .take_while(|&i| i == 0 || fits(i - 1, candidate[i - 1]))
+            .last()
+            .unwrap_or(0);
+        if prefix_fits == PASSWORD_LEN {
+            return Some(*candidate);
+        }
 
-fn valid_password(input: &str) -> bool {
-    no_forbidden_letters(input) && has_incrementing_triplet(input) && has_different_pairs(input)
+        // keep the longest fitting prefix, bump the next letter and fill the rest minimally
+        for i in (0..=prefix_fits.min(PASSWORD_LEN - 1)).rev() {
+            let bumped = (candidate[i] + 1..=b'z').find(|&c| fits(i, c));
+            if let Some(letter) = bumped {
+                let mut result = *candidate;
+                result[i] = letter;
+                for (j, slot) in result.iter_mut().enumerate().skip(i + 1) {
+                    *slot = smallest(j)?;
+                }
+                return Some(result);
+            }
+        }
+        None
+    }
 }
 
-fn has_incrementing_triplet(input: &str) -> bool {
-    input
-        .chars()
-        .collect::<Vec<char>>()
-        .windows(3)
-        .any(|w| is_triplet(w.iter().collect::<String>()))
+impl<'a> Iterator for Passwords<'a> {
+    type Item = String;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut candidate = increment(&self.current?);
+        loop {
+            let found = candidate.and_then(|c| self.next_with_straight(&c));
+            match found {
+                None => {
+                    self.current = None;
+                    return None;
+                }
+                Some(password) if self.rules.is_valid(&password) => {
+                    self.current = Some(password);
+                    return Some(String::from_utf8_lossy(&password).to_string());
+                }
+                Some(password) => candidate = increment(&password),
+            }
+        }
+    }
 }
 
-fn has_different_pairs(input: &str) -> bool {
-    input
-        .chars()
-        .collect::<Vec<char>>()
-        .windows(2)
-        .filter(|c| c[0] == c[1])
-        .unique()
-        .count()
-        >= 2
+pub fn next_passwords(input: &str, count: usize, rules: &PasswordRules) -> Vec<String> {
+    Passwords::after(input, rules).take(count).collect()
 }
 
-fn no_forbidden_letters(input: &str) -> bool {
-    !["i", "o", "l"]
-        .iter()
-        .any(|c| input.contains(&c.to_string()))
+fn parse_password(input: &str) -> [u8; PASSWORD_LEN] {
+    let bytes = input.trim().as_bytes();
+    if bytes.len() != PASSWORD_LEN || !bytes.iter().all(|c| c.is_ascii_lowercase()) {
+        panic!(
+            "Password must be {} lowercase letters, got {}",
+            PASSWORD_LEN, input
+        );
+    }
+    let mut password = [0; PASSWORD_LEN];
+    password.copy_from_slice(bytes);
+    password
 }
 
-fn is_triplet(input: String) -> bool {
-    input.chars().next().unwrap() as u32 + 1 == input.chars().nth(1).unwrap() as u32
-        && input.chars().nth(1).unwrap() as u32 + 1 == input.chars().nth(2).unwrap() as u32
+fn increment(password: &[u8; PASSWORD_LEN]) -> Option<[u8; PASSWORD_LEN]> {
+    let mut result = *password;
+    for c in result.iter_mut().rev() {
+        if *c == b'z' {
+            *c = b'a';
+        } else {
+            *c += 1;
+            return Some(result);
+        }
+    }
+    None
 }
 
 #[cfg(test)]
 mod tests {
     use crate::aoc_2015::day11::{
-        has_different_pairs, has_incrementing_triplet, increment_password, is_triplet,
-        next_in_alphabet, no_forbidden_letters,
+        increment, next_passwords, parse_password, PasswordRules, Passwords,
     };
 
+    fn brute_force(input: &str, count: usize, rules: &PasswordRules) -> Vec<String> {
+        let mut current = Some(parse_password(input));
+        let mut found = vec![];
+        while found.len() < count {
+            current = increment(&current.unwrap());
+            match current {
+                Some(password) if rules.is_valid(&password) => {
+                    found.push(String::from_utf8_lossy(&password).to_string())
+                }
+                Some(_) => continue,
+                None => break,
+            }
+        }
+        found
+    }
+
     #[test]
-    fn test_next_in_alphabet_returns_proper_next_letter() {
+    fn test_increment_carries() {
         // given
-        let letter = 'a';
+        let input = parse_password("abcdefzz");
 
         // when
-        let result = next_in_alphabet(letter);
+        let result = increment(&input).unwrap();
 
         // then
-        let expected = 'b';
-        assert_eq!(result, expected);
+        assert_eq!(&result, b"abcdegaa");
+        assert_eq!(increment(b"zzzzzzzz"), None);
+    }
+
+    #[test]
+    fn test_default_rules() {
+        let rules = PasswordRules::default();
+        assert!(!rules.is_valid(b"hijklmmn"));
+        assert!(!rules.is_valid(b"abbceffg"));
+        assert!(!rules.is_valid(b"abbcegjk"));
+        assert!(rules.is_valid(b"abcdffaa"));
+        assert!(rules.is_valid(b"ghjaabcc"));
+        assert!(!rules.is_valid(b"abcdaaaa"));
+    }
+
+    #[test]
+    fn test_next_password() {
+        let rules = PasswordRules::default();
+        assert_eq!(next_passwords("abcdefgh", 1, &rules), vec!["abcdffaa"]);
+        assert_eq!(next_passwords("ghijklmn", 1, &rules), vec!["ghjaabcc"]);
+    }
+
+    #[test]
+    fn test_next_n_matches_brute_force() {
+        // given
+        let rules = PasswordRules::default();
+
+        // when
+        let result = next_passwords("xxyzzaaa", 20, &rules);
+
+        // then
+        assert_eq!(result, brute_force("xxyzzaaa", 20, &rules));
+    }
+
+    #[test]
+    fn test_custom_rules_match_brute_force() {
+        // given
+        let rules = PasswordRules {
+            forbidden: b"aeu".to_vec(),
+            straight_len: 4,
+            pair_count: 1,
+        };
+
+        // when
+        let result = next_passwords("zzzbbbbb", 10, &rules);
+
+        // then
+        assert_eq!(result, brute_force("zzzbbbbb", 10, &rules));
+        assert!(result.iter().all(|p| !p.contains('a') && !p.contains('e')));
+    }
+
+    #[test]
+    fn test_iterator_ends_after_last_password() {
+        let rules = PasswordRules {
+            forbidden: vec![],
+            straight_len: 1,
+            pair_count: 0,
+        };
+        let passwords: Vec<String> = Passwords::after("zzzzzzzx", &rules).collect();
+        assert_eq!(passwords, vec!["zzzzzzzy", "zzzzzzzz"]);
     }
 
     #[test]
-    fn test_next_in_alphabet_returns_y_for_x() {
+    fn test_increment_returns_next_letter() {
         // given
-        let letter = 'x';
+        let input = parse_password("aaaaaaaa");
 
         // when
-        let result = next_in_alphabet(letter);
+        let result = increment(&input).unwrap();
 
         // then
-        let expected = 'y';
-        assert_eq!(result, expected);
+        assert_eq!(&result, b"aaaaaaab");
     }
 
     #[test]
-    fn test_next_in_alphabet_wraps_around_to_a_from_z() {
+    fn test_increment_returns_y_for_x() {
         // given
-        let letter = 'z';
+        let input = parse_password("aaaaaaax");
 
         // when
-        let result = next_in_alphabet(letter);
+        let result = increment(&input).unwrap();
 
         // then
-        let expected = 'a';
-        assert_eq!(result, expected);
+        assert_eq!(&result, b"aaaaaaay");
     }
 
     #[test]
-    fn test_increment_str_returns_proper_str() {
+    fn test_increment_wraps_around_to_a_from_z() {
         // given
-        let input = "xx".to_string();
+        let input = parse_password("aaaaaaaz");
 
         // when
-        let result = increment_password(input);
+        let result = increment(&input).unwrap();
 
         // then
-        let expected = "xy";
-        assert_eq!(result.as_str(), expected);
+        assert_eq!(&result, b"aaaaaaba");
     }
 
     #[test]
-    fn test_increment_str_returns_ya_for_xz() {
+    fn test_increment_password_returns_proper_password() {
         // given
-        let input = "xz".to_string();
+        let input = parse_password("aaaaaaxx");
 
         // when
-        let result = increment_password(input);
+        let result = increment(&input).unwrap();
 
         // then
-        let expected = "ya";
-        assert_eq!(result.as_str(), expected);
+        assert_eq!(&result, b"aaaaaaxy");
     }
 
     #[test]
-    fn test_is_triplet_returns_true_for_aaa() {
+    fn test_increment_password_returns_ya_for_xz() {
         // given
-        let input = "aaa".to_string();
+        let input = parse_password("aaaaaaxz");
 
         // when
-        let result = is_triplet(input);
+        let result = increment(&input).unwrap();
 
         // then
-        assert_eq!(result, false);
+        assert_eq!(&result, b"aaaaaaya");
     }
 
     #[test]
-    fn test_is_triplet_returns_true_for_abc() {
+    fn test_straight_rejects_aaa() {
         // given
-        let input = "abc".to_string();
+        let input = b"aaa";
 
         // when
-        let result = is_triplet(input);
+        let result = PasswordRules::default().has_straight(input);
 
         // then
-        assert_eq!(result, true);
+        assert!(!result);
     }
 
     #[test]
-    fn test_has_triplet() {
+    fn test_straight_accepts_abc() {
         // given
-        let input = "abc";
+        let input = b"abc";
 
         // when
-        let result = has_incrementing_triplet(input);
+        let result = PasswordRules::default().has_straight(input);
 
         // then
-        assert_eq!(result, true);
+        assert!(result);
     }
 
     #[test]
-    fn test_has_triplet_full_pwd() {
+    fn test_has_straight_full_password() {
         // given
-        let input = "dffaaabc";
+        let input = b"dffaaabc";
 
         // when
-        let result = has_incrementing_triplet(input);
+        let result = PasswordRules::default().has_straight(input);
 
         // then
-        assert_eq!(result, true);
+        assert!(result);
     }
 
     #[test]
-    fn test_has_triplet_full_no_triplet() {
+    fn test_has_straight_full_no_straight() {
         // given
-        let input = "dffaaabd";
+        let input = b"dffaaabd";
 
         // when
-        let result = has_incrementing_triplet(input);
+        let result = PasswordRules::default().has_straight(input);
 
         // then
-        assert_eq!(result, false);
+        assert!(!result);
     }
 
     #[test]
-    fn test_has_different_pairs_some_pairs() {
+    fn test_has_pairs_two_different_pairs() {
         // given
-        let input = "abbceffg";
+        let input = b"abbceffg";
 
         // when
-        let result = has_different_pairs(input);
+        let result = PasswordRules::default().has_pairs(input);
 
         // then
-        assert_eq!(result, true);
+        assert!(result);
     }
 
     #[test]
-    fn test_has_different_pairs_no_pairs() {
+    fn test_has_pairs_overlapping_pair_counts_once() {
         // given
-        let input = "abbbefeg";
+        let input = b"abbbefeg";
 
         // when
-        let result = has_different_pairs(input);
+        let result = PasswordRules::default().has_pairs(input);
 
         // then
-        assert_eq!(result, false);
+        assert!(!result);
     }
 
     #[test]
-    fn test_no_forbidden_has_some_forbidden() {
+    fn test_has_no_forbidden_with_forbidden_letter() {
         // given
-        let input = "ibbbefeg";
+        let input = b"ibbbefeg";
 
         // when
-        let result = no_forbidden_letters(input);
+        let result = PasswordRules::default().has_no_forbidden(input);
 
         // then
-        assert_eq!(result, false);
+        assert!(!result);
     }
 
     #[test]
-    fn test_no_forbidden_has_only_allowed() {
+    fn test_has_no_forbidden_with_only_allowed() {
         // given
-        let input = "abbbefeg";
+        let input = b"abbbefeg";
 
         // when
-        let result = no_forbidden_letters(input);
+        let result = PasswordRules::default().has_no_forbidden(input);
 
         // then
-        assert_eq!(result, true);
+        assert!(result);
     }
 }
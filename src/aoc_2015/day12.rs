@@ -1,22 +1,74 @@
-use lazy_static::lazy_static;
-use regex::Regex;
+use crate::common::json::JsonValue;
 
 pub fn run(input: &str) {
-    let sum = sum_nums(input);
+    let document = parse_document(input);
+    let sum = sum_nums(&document);
     println!("Sum: {}", sum);
+    let sum_without_red = sum_nums_without_red(&document);
+    println!("Sum without red objects: {}", sum_without_red);
 }
 
-fn sum_nums(s: &str) -> i64 {
-    let nums = extract_nums(s);
-    nums.iter().sum()
+fn parse_document(input: &str) -> JsonValue {
+    input
+        .parse()
+        .unwrap_or_else(|e| panic!("Invalid JSON document: {}", e))
 }
 
-fn extract_nums(s: &str) -> Vec<i64> {
-    lazy_static! {
-        static ref RE: Regex = Regex::new(r"-?\d+").unwrap();
+fn add_number(acc: i64, value: &JsonValue) -> i64 {
+    match value {
+        JsonValue::Number(n) => acc + *n as i64,
+        _ => acc,
     }
+}
+
+fn is_not_red(fields: &[(String, JsonValue)]) -> bool {
+    !fields
+        .iter()
+        .any(|(_, v)| matches!(v, JsonValue::String(s) if s == "red"))
+}
+
+fn sum_nums(document: &JsonValue) -> i64 {
+    document.fold(0, &mut add_number)
+}
+
+fn sum_nums_without_red(document: &JsonValue) -> i64 {
+    document.fold_filtered(0, &is_not_red, &mut add_number)
+}
 
-    RE.find_iter(s)
-        .filter_map(|digits| digits.as_str().parse().ok())
-        .collect()
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sum_nums() {
+        let cases = [
+            ("[1,2,3]", 6),
+            ("{\"a\":2,\"b\":4}", 6),
+            ("[[[3]]]", 3),
+            ("{\"a\":{\"b\":4},\"c\":-1}", 3),
+            ("{\"a\":[-1,1]}", 0),
+            ("[-1,{\"a\":1}]", 0),
+            ("[]", 0),
+            ("{}", 0),
+        ];
+
+        for (input, expected) in cases {
+            assert_eq!(sum_nums(&parse_document(input)), expected, "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_sum_nums_without_red() {
+        let cases = [
+            ("[1,2,3]", 6),
+            ("[1,{\"c\":\"red\",\"b\":2},3]", 4),
+            ("{\"d\":\"red\",\"e\":[1,2,3,4],\"f\":5}", 0),
+            ("[1,\"red\",5]", 6),
+        ];
+
+        for (input, expected) in cases {
+            let document = parse_document(input);
+            assert_eq!(sum_nums_without_red(&document), expected, "{}", input);
+        }
+    }
 }
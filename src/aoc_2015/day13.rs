@@ -11,33 +11,40 @@ pub struct Happiness {
 pub struct AsymmetricHappinessMatrix {
     name_lookup: HashMap<String, usize>,
     matrix: Vec<Vec<i64>>,
-    cities: Vec<String>,
+    guests: Vec<String>,
 }
 
-impl AsymmetricHappinessMatrix {
-    fn new(distances: Vec<Happiness>) -> AsymmetricHappinessMatrix {
-        let guests = AsymmetricHappinessMatrix::uniq_guests(&distances);
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NeighbourHappiness {
+    pub left: String,
+    pub right: String,
+    pub left_to_right: i64,
+    pub right_to_left: i64,
+}
 
-        let name_lookup: HashMap<_, _> = guests
-            .iter()
-            .enumerate()
-            .map(|(i, c)| (c.to_string(), i))
-            .collect();
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Seating {
+    pub guests: Vec<String>,
+    pub pairs: Vec<NeighbourHappiness>,
+    pub total: i64,
+}
 
-        let matrix_size = guests.len();
-        let mut matrix: Vec<Vec<i64>> = vec![vec![0; matrix_size]; matrix_size];
+impl AsymmetricHappinessMatrix {
+    fn new(distances: Vec<Happiness>) -> AsymmetricHappinessMatrix {
+        let mut matrix = AsymmetricHappinessMatrix {
+            name_lookup: HashMap::new(),
+            matrix: vec![],
+            guests: vec![],
+        };
 
+        for guest in AsymmetricHappinessMatrix::uniq_guests(&distances) {
+            matrix.add_guest(&guest);
+        }
         for distance in &distances {
-            let from_idx = *name_lookup.get(&distance.from).unwrap();
-            let to_idx = *name_lookup.get(&distance.to).unwrap();
-            matrix[from_idx][to_idx] = distance.delta;
+            matrix.set_happiness(&distance.from, &distance.to, distance.delta);
         }
 
-        AsymmetricHappinessMatrix {
-            name_lookup,
-            matrix,
-            cities: guests,
-        }
+        matrix
     }
 
     fn uniq_guests(distances: &[Happiness]) -> Vec<String> {
@@ -52,51 +59,178 @@ impl AsymmetricHappinessMatrix {
             .collect()
     }
 
+    // new guests feel nothing about anyone and nobody feels anything about them
+    pub fn add_guest(&mut self, name: &str) -> bool {
+        if self.name_lookup.contains_key(name) {
+            return false;
+        }
+
+        self.name_lookup.insert(name.to_string(), self.guests.len());
+        self.guests.push(name.to_string());
+        for row in self.matrix.iter_mut() {
+            row.push(0);
+        }
+        self.matrix.push(vec![0; self.guests.len()]);
+        true
+    }
+
+    pub fn remove_guest(&mut self, name: &str) -> bool {
+        let index = match self.name_lookup.remove(name) {
+            Some(index) => index,
+            None => return false,
+        };
+
+        self.guests.remove(index);
+        self.matrix.remove(index);
+        for row in self.matrix.iter_mut() {
+            row.remove(index);
+        }
+        for (i, guest) in self.guests.iter().enumerate() {
+            self.name_lookup.insert(guest.to_string(), i);
+        }
+        true
+    }
+
+    pub fn set_happiness(&mut self, from: &str, to: &str, delta: i64) {
+        let from_idx = self.index(from);
+        let to_idx = self.index(to);
+        self.matrix[from_idx][to_idx] = delta;
+    }
+
+    fn index(&self, guest: &str) -> usize {
+        *self
+            .name_lookup
+            .get(guest)
+            .unwrap_or_else(|| panic!("Unknown guest {}", guest))
+    }
+
     fn happiness(&self, from: usize, to: usize) -> i64 {
         self.matrix[from][to]
     }
 
     fn happiness_guests(&self, from: &str, to: &str) -> i64 {
-        self.happiness(
-            *self.name_lookup.get(from).unwrap(),
-            *self.name_lookup.get(to).unwrap(),
-        )
+        self.happiness(self.index(from), self.index(to))
     }
 
-    fn get_seating_delta(&self, cities: Vec<&str>) -> i64 {
-        let in_line: i64 = cities
+    fn pair_happiness(&self, first: usize, second: usize) -> i64 {
+        self.happiness(first, second) + self.happiness(second, first)
+    }
+
+    fn get_seating_delta(&self, guests: &[&str]) -> i64 {
+        if guests.len() < 2 {
+            return 0;
+        }
+
+        let in_line: i64 = guests
             .windows(2)
             .map(|pair| {
                 self.happiness_guests(pair[0], pair[1]) + self.happiness_guests(pair[1], pair[0])
             })
             .sum();
 
-        let close: i64 = self.happiness_guests(cities.last().unwrap(), cities.first().unwrap())
-            + self.happiness_guests(cities.first().unwrap(), cities.last().unwrap());
+        let close: i64 = self.happiness_guests(guests.last().unwrap(), guests.first().unwrap())
+            + self.happiness_guests(guests.first().unwrap(), guests.last().unwrap());
 
         in_line + close
     }
 
-    fn solve_max(&self) {
-        let mut best = 0;
-        for perm in self.cities.iter().permutations(self.cities.len()).unique() {
-            let v2 = perm.iter().map(|s| s.as_str()).collect();
-            let tour = self.get_seating_delta(v2);
-            if tour > best {
-                best = tour;
+    // Held-Karp over subsets, the table is indexed by visited set and last seated guest,
+    // the first guest is fixed since rotations of a round table are equivalent
+    fn solve_max(&self) -> Seating {
+        let n = self.guests.len();
+        if n < 2 {
+            return self.seating(&(0..n).collect::<Vec<_>>());
+        }
+
+        let full = (1 << n) - 1;
+        let mut best: Vec<Vec<Option<i64>>> = vec![vec![None; n]; 1 << n];
+        let mut previous: Vec<Vec<usize>> = vec![vec![0; n]; 1 << n];
+        best[1][0] = Some(0);
+
+        for mask in (1..=full).filter(|m| m & 1 == 1) {
+            for last in 0..n {
+                let current = match best[mask][last] {
+                    Some(value) => value,
+                    None => continue,
+                };
+                for next in (0..n).filter(|next| mask & (1 << next) == 0) {
+                    let next_mask = mask | (1 << next);
+                    let value = current + self.pair_happiness(last, next);
+                    if !matches!(best[next_mask][next], Some(b) if b >= value) {
+                        best[next_mask][next] = Some(value);
+                        previous[next_mask][next] = last;
+                    }
+                }
             }
         }
-        println!("Longest route: {}", best)
+
+        let last = (1..n)
+            .max_by_key(|&last| best[full][last].unwrap() + self.pair_happiness(last, 0))
+            .unwrap();
+
+        let mut order = vec![];
+        let mut mask = full;
+        let mut current = last;
+        while current != 0 {
+            order.push(current);
+            let before = previous[mask][current];
+            mask &= !(1 << current);
+            current = before;
+        }
+        order.push(0);
+        order.reverse();
+
+        self.seating(&order)
+    }
+
+    fn seating(&self, order: &[usize]) -> Seating {
+        let guests: Vec<String> = order.iter().map(|&i| self.guests[i].to_string()).collect();
+        let names: Vec<&str> = guests.iter().map(|g| g.as_str()).collect();
+        let pairs = match order.len() {
+            0 | 1 => vec![],
+            len => (0..len)
+                .map(|i| {
+                    let (left, right) = (order[i], order[(i + 1) % len]);
+                    NeighbourHappiness {
+                        left: self.guests[left].to_string(),
+                        right: self.guests[right].to_string(),
+                        left_to_right: self.happiness(left, right),
+                        right_to_left: self.happiness(right, left),
+                    }
+                })
+                .collect(),
+        };
+
+        Seating {
+            total: self.get_seating_delta(&names),
+            guests,
+            pairs,
+        }
     }
 }
 
 pub fn run(input: &[String]) {
     let distances = parse_input(input);
-    println!("{:?}", distances);
-    let matrix = AsymmetricHappinessMatrix::new(distances);
+    let mut matrix = AsymmetricHappinessMatrix::new(distances);
     println!("Alice -> Bob: {}", matrix.happiness_guests("Alice", "Bob"));
-    // matrix.solve_min();
-    matrix.solve_max();
+
+    let seating = matrix.solve_max();
+    println!("Best happiness: {}", seating.total);
+    for pair in &seating.pairs {
+        println!(
+            "  {} -> {}: {}, {} -> {}: {}",
+            pair.left, pair.right, pair.left_to_right, pair.right, pair.left, pair.right_to_left
+        );
+    }
+
+    matrix.add_guest("Yourself");
+    let with_yourself = matrix.solve_max();
+    println!(
+        "Best happiness with yourself: {} ({})",
+        with_yourself.total,
+        with_yourself.guests.join(", ")
+    );
+    matrix.remove_guest("Yourself");
 }
 
 fn parse_input(input: &[String]) -> Vec<Happiness> {
@@ -117,3 +251,120 @@ fn parse_input(input: &[String]) -> Vec<Happiness> {
         })
         .collect()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::parse::test_utils::vec_of_strings;
+
+    fn example_matrix() -> AsymmetricHappinessMatrix {
+        let input = vec_of_strings![
+            "Alice would gain 54 happiness units by sitting next to Bob.",
+            "Alice would lose 79 happiness units by sitting next to Carol.",
+            "Alice would lose 2 happiness units by sitting next to David.",
+            "Bob would gain 83 happiness units by sitting next to Alice.",
+            "Bob would lose 7 happiness units by sitting next to Carol.",
+            "Bob would lose 63 happiness units by sitting next to David.",
+            "Carol would lose 62 happiness units by sitting next to Alice.",
+            "Carol would gain 60 happiness units by sitting next to Bob.",
+            "Carol would gain 55 happiness units by sitting next to David.",
+            "David would gain 46 happiness units by sitting next to Alice.",
+            "David would lose 7 happiness units by sitting next to Bob.",
+            "David would gain 41 happiness units by sitting next to Carol."
+        ];
+        AsymmetricHappinessMatrix::new(parse_input(&input))
+    }
+
+    fn brute_force(matrix: &AsymmetricHappinessMatrix) -> i64 {
+        matrix
+            .guests
+            .iter()
+            .map(|g| g.as_str())
+            .permutations(matrix.guests.len())
+            .map(|p| matrix.get_seating_delta(&p))
+            .max()
+            .unwrap_or(0)
+    }
+
+    #[test]
+    fn test_solve_max() {
+        // given
+        let matrix = example_matrix();
+
+        // when
+        let seating = matrix.solve_max();
+
+        // then
+        assert_eq!(seating.total, 330);
+        assert_eq!(seating.guests.len(), 4);
+        assert_eq!(seating.pairs.len(), 4);
+        let breakdown: i64 = seating
+            .pairs
+            .iter()
+            .map(|p| p.left_to_right + p.right_to_left)
+            .sum();
+        assert_eq!(breakdown, 330);
+    }
+
+    #[test]
+    fn test_add_and_remove_guest() {
+        // given
+        let mut matrix = example_matrix();
+
+        // when
+        assert!(matrix.add_guest("Yourself"));
+        assert!(!matrix.add_guest("Yourself"));
+        let with_yourself = matrix.solve_max();
+
+        // then
+        assert_eq!(with_yourself.total, brute_force(&matrix));
+        assert!(with_yourself.guests.contains(&"Yourself".to_string()));
+        assert!(matrix.remove_guest("Yourself"));
+        assert!(!matrix.remove_guest("Yourself"));
+        assert_eq!(matrix.solve_max().total, 330);
+    }
+
+    #[test]
+    fn test_remove_reindexes_guests() {
+        // given
+        let mut matrix = example_matrix();
+
+        // when
+        matrix.remove_guest("Bob");
+
+        // then
+        assert_eq!(matrix.happiness_guests("Carol", "David"), 55);
+        assert_eq!(matrix.happiness_guests("David", "Alice"), 46);
+        assert_eq!(matrix.solve_max().total, brute_force(&matrix));
+    }
+
+    #[test]
+    fn test_matches_brute_force() {
+        // given
+        let mut matrix = example_matrix();
+        let extra = ["Eve", "Frank", "Grace"];
+        for (i, guest) in extra.iter().enumerate() {
+            matrix.add_guest(guest);
+            for (j, other) in ["Alice", "Bob", "Carol", "David"].iter().enumerate() {
+                let delta = ((i * 7 + j * 13) % 23) as i64 - 11;
+                matrix.set_happiness(guest, other, delta);
+                matrix.set_happiness(other, guest, -delta / 2);
+            }
+        }
+
+        // then
+        assert_eq!(matrix.solve_max().total, brute_force(&matrix));
+    }
+
+    #[test]
+    fn test_small_tables() {
+        let mut matrix = example_matrix();
+        for guest in ["Alice", "Bob", "Carol"] {
+            matrix.remove_guest(guest);
+        }
+        assert_eq!(matrix.solve_max().total, 0);
+        assert!(matrix.solve_max().pairs.is_empty());
+        matrix.remove_guest("David");
+        assert_eq!(matrix.solve_max().guests.len(), 0);
+    }
+}
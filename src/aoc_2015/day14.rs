@@ -1,80 +1,148 @@
-pub fn run(input: &[String]) {
-    let mut reindeers = parse_input(input);
+use std::io;
+use std::io::Write;
+use std::path::Path;
 
-    let max = reindeers
-        .iter_mut()
-        .map(|r| r.run(2503))
-        .inspect(|d| println!("Distance: {}", d))
-        .max()
-        .unwrap();
+use crate::common::data::export;
 
-    println!("Max distance: {}", max)
-}
+const RACE_DURATION: usize = 2503;
 
-#[derive(Debug)]
-enum State {
-    Running,
-    Resting,
-}
+pub fn run(input: &[String], export_dir: Option<&Path>) {
+    let race = Race::new(parse_input(input));
 
-impl Default for State {
-    fn default() -> Self {
-        State::Running
+    for reindeer in &race.reindeer {
+        println!(
+            "{} distance: {}",
+            reindeer.name,
+            reindeer.distance_at(RACE_DURATION)
+        );
+    }
+
+    let (name, max) = race.max_distance(RACE_DURATION);
+    println!("Max distance: {} by {}", max, name);
+
+    let (name, points) = race.max_points(RACE_DURATION);
+    println!("Max points: {} by {}", points, name);
+
+    if let Some(dir) = export_dir {
+        export(dir, "aoc_2015_day14_race.csv", |out| {
+            race.write_csv(RACE_DURATION, out)
+        });
     }
 }
 
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Clone)]
 struct Reindeer {
+    name: String,
     speed: usize,
     run_time: usize,
     rest_time: usize,
-    rest_budget: usize,
-    run_budget: usize,
-    state: State,
 }
 
 impl Reindeer {
-    pub fn run(&mut self, seconds: usize) -> usize {
-        let res = (0..=seconds)
-            .collect::<Vec<usize>>()
-            .iter()
-            .map(|_| self.tick())
-            .sum();
+    pub fn distance_at(&self, seconds: usize) -> usize {
+        let cycle = self.run_time + self.rest_time;
+        // a reindeer that neither runs nor rests never leaves the start
+        if cycle == 0 {
+            return 0;
+        }
+        let full_cycles = seconds / cycle;
+        let remainder = seconds % cycle;
+
+        self.speed * (full_cycles * self.run_time + remainder.min(self.run_time))
+    }
+}
 
-        self.reset();
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Standing {
+    pub second: usize,
+    pub distances: Vec<usize>,
+    pub points: Vec<usize>,
+    pub leaders: Vec<usize>,
+}
+
+struct Race {
+    reindeer: Vec<Reindeer>,
+}
 
-        res
+impl Race {
+    pub fn new(reindeer: Vec<Reindeer>) -> Race {
+        if reindeer.is_empty() {
+            panic!("Race needs at least one reindeer");
+        }
+        Race { reindeer }
     }
 
-    pub fn reset(&mut self) {
-        self.run_budget = 0;
-        self.rest_budget = 0;
-        self.state = State::Running;
+    pub fn max_distance(&self, seconds: usize) -> (String, usize) {
+        let winner = self
+            .reindeer
+            .iter()
+            .max_by_key(|r| r.distance_at(seconds))
+            .unwrap();
+        (winner.name.clone(), winner.distance_at(seconds))
     }
 
-    pub fn tick(&mut self) -> usize {
-        match self.state {
-            State::Running => {
-                self.run_budget += 1;
+    pub fn max_points(&self, seconds: usize) -> (String, usize) {
+        let points = self
+            .timeline(seconds)
+            .pop()
+            .map_or(vec![0; self.reindeer.len()], |s| s.points);
+        let (winner, max) = points.iter().enumerate().max_by_key(|(_, p)| **p).unwrap();
+        (self.reindeer[winner].name.clone(), *max)
+    }
 
-                if self.run_budget == self.run_time {
-                    self.run_budget = 0;
-                    self.state = State::Resting;
-                }
+    pub fn timeline(&self, seconds: usize) -> Vec<Standing> {
+        let mut points = vec![0; self.reindeer.len()];
 
-                self.speed
-            }
-            State::Resting => {
-                self.rest_budget += 1;
+        (1..=seconds)
+            .map(|second| {
+                let distances: Vec<usize> = self
+                    .reindeer
+                    .iter()
+                    .map(|r| r.distance_at(second))
+                    .collect();
+                let lead = *distances.iter().max().unwrap();
+                let leaders: Vec<usize> = (0..distances.len())
+                    .filter(|&i| distances[i] == lead)
+                    .collect();
+                for &leader in &leaders {
+                    points[leader] += 1;
+                }
 
-                if self.rest_budget == self.rest_time {
-                    self.rest_budget = 0;
-                    self.state = State::Running
+                Standing {
+                    second,
+                    distances,
+                    points: points.clone(),
+                    leaders,
                 }
+            })
+            .collect()
+    }
 
-                0
-            }
+    pub fn write_csv<W: Write>(&self, seconds: usize, out: &mut W) -> io::Result<()> {
+        let names: Vec<&str> = self.reindeer.iter().map(|r| r.name.as_str()).collect();
+        let distance_headers = names.iter().map(|n| format!("{} distance", n));
+        let point_headers = names.iter().map(|n| format!("{} points", n));
+        let header: Vec<String> = distance_headers.chain(point_headers).collect();
+        writeln!(out, "second,{},leaders", header.join(","))?;
+
+        for standing in self.timeline(seconds) {
+            let values: Vec<String> = standing
+                .distances
+                .iter()
+                .chain(standing.points.iter())
+                .map(|v| v.to_string())
+                .collect();
+            let leaders: Vec<&str> = standing.leaders.iter().map(|&i| names[i]).collect();
+            writeln!(
+                out,
+                "{},{},{}",
+                standing.second,
+                values.join(","),
+                leaders.join(" ")
+            )?;
         }
+
+        Ok(())
     }
 }
 
@@ -84,26 +152,28 @@ fn parse_input(input: &[String]) -> Vec<Reindeer> {
 
 fn to_reindeer(input: &str) -> Reindeer {
     let tokens: Vec<&str> = input.split(' ').collect();
+    let name = tokens[0].to_string();
     let speed: usize = tokens[3].parse().unwrap();
     let run_time: usize = tokens[6].parse().unwrap();
     let rest_time: usize = tokens[13].parse().unwrap();
 
     Reindeer {
+        name,
         speed,
         run_time,
         rest_time,
-        ..Default::default()
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::aoc_2015::day14::Reindeer;
+    use crate::aoc_2015::day14::{parse_input, Race, Reindeer};
+    use crate::common::parse::test_utils::vec_of_strings;
 
     #[test]
     fn test_travels_full_amount() {
         // given
-        let mut r = Reindeer {
+        let r = Reindeer {
             speed: 10,
             run_time: 1,
             rest_time: 1,
@@ -111,13 +181,13 @@ mod tests {
         };
 
         // then
-        assert_eq!(r.run(1), 10);
+        assert_eq!(r.distance_at(1), 10);
     }
 
     #[test]
     fn test_travels_full_distance_rests_and_travels_again() {
         // given
-        let mut r = Reindeer {
+        let r = Reindeer {
             speed: 10,
             run_time: 1,
             rest_time: 1,
@@ -125,13 +195,13 @@ mod tests {
         };
 
         // then
-        assert_eq!(r.run(3), 20);
+        assert_eq!(r.distance_at(3), 20);
     }
 
     #[test]
     fn test_travels_full_distance_rests_and_travels_again_twice() {
         // given
-        let mut r = Reindeer {
+        let r = Reindeer {
             speed: 10,
             run_time: 1,
             rest_time: 1,
@@ -139,6 +209,106 @@ mod tests {
         };
 
         // then
-        assert_eq!(r.run(5), 30);
+        assert_eq!(r.distance_at(5), 30);
+    }
+
+    #[test]
+    fn test_zero_cycle_stays_at_start() {
+        // given
+        let r = Reindeer {
+            speed: 10,
+            ..Default::default()
+        };
+
+        // then
+        assert_eq!(r.distance_at(5), 0);
+    }
+
+    fn example_race() -> Race {
+        let input = vec_of_strings![
+            "Comet can fly 14 km/s for 10 seconds, but then must rest for 127 seconds.",
+            "Dancer can fly 16 km/s for 11 seconds, but then must rest for 162 seconds."
+        ];
+        Race::new(parse_input(&input))
+    }
+
+    #[test]
+    fn test_distance_closed_form_matches_ticks() {
+        // given
+        let r = Reindeer {
+            speed: 7,
+            run_time: 3,
+            rest_time: 4,
+            ..Default::default()
+        };
+
+        // when
+        let mut ticked = 0;
+        for second in 1..=50 {
+            if (second - 1) % 7 < 3 {
+                ticked += 7;
+            }
+
+            // then
+            assert_eq!(r.distance_at(second), ticked);
+        }
+    }
+
+    #[test]
+    fn test_max_distance() {
+        let race = example_race();
+        assert_eq!(race.max_distance(1000), ("Comet".to_string(), 1120));
+    }
+
+    #[test]
+    fn test_points() {
+        // given
+        let race = example_race();
+
+        // when
+        let timeline = race.timeline(1000);
+
+        // then
+        let last = timeline.last().unwrap();
+        assert_eq!(last.points, vec![312, 689]);
+        assert_eq!(timeline[0].leaders, vec![1]);
+        assert_eq!(timeline[139].leaders, vec![0]);
+        assert_eq!(race.max_points(1000), ("Dancer".to_string(), 689));
+    }
+
+    #[test]
+    fn test_ties_score_for_every_leader() {
+        // given
+        let input = vec_of_strings![
+            "A can fly 5 km/s for 2 seconds, but then must rest for 1 seconds.",
+            "B can fly 5 km/s for 2 seconds, but then must rest for 1 seconds.",
+            "C can fly 4 km/s for 3 seconds, but then must rest for 1 seconds."
+        ];
+        let race = Race::new(parse_input(&input));
+
+        // when
+        let timeline = race.timeline(3);
+
+        // then
+        assert_eq!(timeline[0].leaders, vec![0, 1]);
+        assert_eq!(timeline[2].distances, vec![10, 10, 12]);
+        assert_eq!(timeline[2].points, vec![2, 2, 1]);
+    }
+
+    #[test]
+    fn test_csv_export() {
+        // given
+        let race = example_race();
+        let mut out = vec![];
+
+        // when
+        race.write_csv(2, &mut out).unwrap();
+
+        // then
+        let csv = String::from_utf8(out).unwrap();
+        let expected = "second,Comet distance,Dancer distance,Comet points,Dancer points,leaders\n\
+                        1,14,16,0,1,Dancer\n\
+                        2,28,32,0,2,Dancer\n";
+        assert_eq!(csv, expected);
     }
 }
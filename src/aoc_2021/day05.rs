@@ -1,4 +1,5 @@
 use crate::common::parse::parse_numbers;
+use std::collections::HashMap;
 
 #[derive(Debug, Clone)]
 pub struct Line {
@@ -17,47 +18,75 @@ impl Line {
         self.start_x == self.end_x
     }
 
-    fn max_x(&self) -> i64 {
-        self.start_x.max(self.end_x)
+    fn points(&self) -> LinePoints {
+        let delta_x = (self.end_x - self.start_x).abs();
+        let delta_y = -(self.end_y - self.start_y).abs();
+
+        LinePoints {
+            x: self.start_x,
+            y: self.start_y,
+            end_x: self.end_x,
+            end_y: self.end_y,
+            delta_x,
+            delta_y,
+            step_x: (self.end_x - self.start_x).signum(),
+            step_y: (self.end_y - self.start_y).signum(),
+            error: delta_x + delta_y,
+            done: false,
+        }
     }
+}
 
-    fn max_y(&self) -> i64 {
-        self.start_y.max(self.end_y)
-    }
+// Bresenham's line algorithm, valid for every octant
+struct LinePoints {
+    x: i64,
+    y: i64,
+    end_x: i64,
+    end_y: i64,
+    delta_x: i64,
+    delta_y: i64,
+    step_x: i64,
+    step_y: i64,
+    error: i64,
+    done: bool,
+}
 
-    fn delta_x(&self) -> i64 {
-        self.end_x - self.start_x
-    }
+impl Iterator for LinePoints {
+    type Item = (i64, i64);
 
-    fn delta_y(&self) -> i64 {
-        self.end_y - self.start_y
-    }
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+
+        let point = (self.x, self.y);
+        if point == (self.end_x, self.end_y) {
+            self.done = true;
+            return Some(point);
+        }
 
-    fn length(&self) -> i64 {
-        i64::max(self.delta_x().abs(), self.delta_y().abs())
+        let doubled_error = 2 * self.error;
+        if doubled_error >= self.delta_y {
+            self.error += self.delta_y;
+            self.x += self.step_x;
+        }
+        if doubled_error <= self.delta_x {
+            self.error += self.delta_x;
+            self.y += self.step_y;
+        }
+
+        Some(point)
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default)]
 struct Grid {
-    width: i64,
-    values: Vec<i64>,
+    counts: HashMap<(i64, i64), usize>,
 }
 
 impl Grid {
-    pub fn new(width: i64, height: i64) -> Grid {
-        let values = vec![0; (width * height) as usize];
-
-        Grid { width, values }
-    }
-
-    fn get_index(&self, x: i64, y: i64) -> i64 {
-        y * self.width + x
-    }
-
-    fn inc_value(&mut self, row: i64, column: i64) {
-        let index = self.get_index(row, column);
-        self.values[index as usize] += 1
+    pub fn new() -> Grid {
+        Grid::default()
     }
 
     fn draw_lines(&mut self, lines: &[Line]) {
@@ -67,19 +96,24 @@ impl Grid {
     }
 
     fn draw_line(&mut self, line: &Line) {
-        let x_delta = (line.delta_x()).signum();
-        let y_delta = (line.delta_y()).signum();
-        let len = line.length();
-
-        for n in 0..=len {
-            let row = line.start_y + n * y_delta;
-            let column = line.start_x + n * x_delta;
-            self.inc_value(row, column);
+        for point in line.points() {
+            *self.counts.entry(point).or_insert(0) += 1;
         }
     }
 
     fn count_overlapping(&self) -> i64 {
-        self.values.iter().filter(|&&v| v > 1).count() as i64
+        self.counts.values().filter(|&&v| v > 1).count() as i64
+    }
+
+    fn overlap_points(&self) -> Vec<(i64, i64)> {
+        let mut points: Vec<(i64, i64)> = self
+            .counts
+            .iter()
+            .filter(|(_, &v)| v > 1)
+            .map(|(&p, _)| p)
+            .collect();
+        points.sort_unstable();
+        points
     }
 }
 
@@ -97,7 +131,13 @@ pub fn run(input: &[String]) {
     let count = count_overlapping(input);
     println!("Num overlapping points: {}", count);
     let count2 = count_overlapping_with_diagonal(input);
-    println!("Num overlapping points: {}", count2)
+    println!("Num overlapping points: {}", count2);
+    let points = overlapping_points(input);
+    println!(
+        "Overlap points span from {:?} to {:?}",
+        points.first(),
+        points.last()
+    );
 }
 
 pub fn count_overlapping(input: &[String]) -> i64 {
@@ -110,8 +150,7 @@ pub fn count_overlapping(input: &[String]) -> i64 {
 }
 
 pub fn count_overlapping_points(lines: &[Line]) -> i64 {
-    let (width, height) = max_coords(lines);
-    let mut grid = Grid::new(width + 1, height + 1);
+    let mut grid = Grid::new();
     grid.draw_lines(lines);
     grid.count_overlapping()
 }
@@ -121,16 +160,11 @@ pub fn count_overlapping_with_diagonal(input: &[String]) -> i64 {
     count_overlapping_points(&lines)
 }
 
-pub fn max_coords(lines: &[Line]) -> (i64, i64) {
-    let mut max_x = 0;
-    let mut max_y = 0;
-
-    for line in lines {
-        max_x = max_x.max(line.max_x());
-        max_y = max_y.max(line.max_y());
-    }
-
-    (max_x, max_y)
+pub fn overlapping_points(input: &[String]) -> Vec<(i64, i64)> {
+    let lines: Vec<_> = input.iter().map(|l| parse_line(l)).collect();
+    let mut grid = Grid::new();
+    grid.draw_lines(&lines);
+    grid.overlap_points()
 }
 
 #[cfg(test)]
@@ -147,7 +181,7 @@ mod tests {
             end_x: 3,
             end_y: 1,
         };
-        let mut grid = Grid::new(4, 4);
+        let mut grid = Grid::new();
 
         // when
         grid.draw_line(&line);
@@ -202,4 +236,48 @@ mod tests {
         //then
         assert_eq!(result, 12);
     }
+
+    #[test]
+    fn test_line_points_any_slope() {
+        // given
+        let line = parse_line("0,0 -> 5,2");
+
+        // when
+        let points: Vec<(i64, i64)> = line.points().collect();
+
+        // then
+        let expected = vec![(0, 0), (1, 0), (2, 1), (3, 1), (4, 2), (5, 2)];
+        assert_eq!(points, expected);
+    }
+
+    #[test]
+    fn test_line_points_steep_reversed() {
+        // given
+        let line = parse_line("2,5 -> 1,0");
+
+        // when
+        let points: Vec<(i64, i64)> = line.points().collect();
+
+        // then
+        assert_eq!(points.len(), 6);
+        assert_eq!(points.first(), Some(&(2, 5)));
+        assert_eq!(points.last(), Some(&(1, 0)));
+        assert!(points.windows(2).all(|w| w[0].1 - w[1].1 == 1));
+    }
+
+    #[test]
+    fn test_overlapping_points_huge_coordinates() {
+        // given
+        let lines = vec_of_strings![
+            "1000000000,0 -> 1000000000,4",
+            "999999998,2 -> 1000000003,2",
+            "999999999,1 -> 1000000001,3"
+        ];
+
+        // when
+        let result = overlapping_points(&lines);
+
+        // then
+        assert_eq!(result, vec![(1000000000, 2)]);
+    }
 }
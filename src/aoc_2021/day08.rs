@@ -1,124 +1,296 @@
-use std::collections::HashSet;
-
-use itertools::Itertools;
+use std::collections::HashMap;
+use std::fmt;
+use std::fmt::Formatter;
 
 pub fn run(input: &[String]) {
     let count = part_1(input);
     println!("Digits appear {} times in output", count);
-    let sum = part_2(input);
-    println!("Total output sum: {}", sum);
+    match part_2(input) {
+        Ok(sum) => println!("Total output sum: {}", sum),
+        Err(e) => println!("Cannot sum outputs: {}", e),
+    }
 }
 
-pub fn part_1(input: &[String]) -> usize {
-    input
-        .iter()
-        .map(|l| {
-            let tokens: Vec<&str> = l.split('|').collect();
-            let out_tokens: Vec<&str> = tokens[1].split(' ').collect();
-
-            out_tokens
-                .iter()
-                .filter(|t| t.len() == 2 || t.len() == 4 || t.len() == 3 || t.len() == 7)
-                .count()
-        })
-        .sum()
+const SEVEN_SEGMENT_DIGITS: [&str; 10] = [
+    "abcefg", "cf", "acdeg", "acdfg", "bcdf", "abdfg", "abdefg", "acf", "abcdefg", "abcdfg",
+];
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum DecodeError {
+    MissingSeparator,
+    UnknownWire(char),
+    InvalidPattern(u32),
+    Contradictory,
+    Ambiguous(Vec<usize>, Vec<usize>),
+    Overflow,
 }
 
-pub fn part_2(input: &[String]) -> usize {
-    input.iter().map(|l| get_line_value(l)).sum()
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            DecodeError::MissingSeparator => write!(f, "missing '|' separator"),
+            DecodeError::UnknownWire(c) => write!(f, "unknown wire '{}'", c),
+            DecodeError::InvalidPattern(mask) => write!(f, "invalid pattern {:#b}", mask),
+            DecodeError::Contradictory => write!(f, "no wiring matches the patterns"),
+            DecodeError::Ambiguous(first, second) => {
+                write!(f, "output reads as both {:?} and {:?}", first, second)
+            }
+            DecodeError::Overflow => write!(f, "output value does not fit in usize"),
+        }
+    }
 }
 
-pub fn from_digits(digits: &[usize]) -> usize {
-    digits
-        .iter()
-        .map(|d| d.to_string())
-        .join("")
-        .parse()
-        .unwrap()
+pub struct SegmentDisplay {
+    wires: Vec<char>,
+    digits: Vec<u32>,
+    digit_of: HashMap<u32, usize>,
 }
 
-pub fn get_line_value(input: &str) -> usize {
-    let tokens: Vec<Vec<String>> = input
-        .splitn(2, '|')
-        .map(|s| s.trim().split(' ').map(|s| s.to_string()).collect())
-        .collect();
+impl SegmentDisplay {
+    pub fn new(wires: &str, digits: &[&str]) -> SegmentDisplay {
+        let wires: Vec<char> = wires.chars().collect();
+        if wires.len() > 32 {
+            panic!("Display can have at most 32 segments, got {}", wires.len());
+        }
+        let mut display = SegmentDisplay {
+            wires,
+            digits: vec![],
+            digit_of: HashMap::new(),
+        };
+        for (digit, segments) in digits.iter().enumerate() {
+            let mask = display
+                .parse_pattern(segments)
+                .unwrap_or_else(|e| panic!("Invalid digit {}: {}", segments, e));
+            if display.digit_of.insert(mask, digit).is_some() {
+                panic!("Digit {} duplicates an earlier digit", segments);
+            }
+            display.digits.push(mask);
+        }
+        display
+    }
 
-    let segments = tokens.get(0).unwrap();
-    let values = tokens.get(1).unwrap();
-    let combination = find_encoding(segments);
-    decode(values, &combination)
-}
+    pub fn seven_segment() -> SegmentDisplay {
+        SegmentDisplay::new("abcdefg", &SEVEN_SEGMENT_DIGITS)
+    }
 
-pub fn decode(segments: &[String], combination: &str) -> usize {
-    let digits: Vec<_> = segments
-        .iter()
-        .map(|s| find_value(s, combination))
-        .collect();
+    pub fn base(&self) -> usize {
+        self.digits.len()
+    }
 
-    from_digits(&digits)
-}
+    pub fn parse_pattern(&self, pattern: &str) -> Result<u32, DecodeError> {
+        pattern
+            .chars()
+            .try_fold(0, |mask, c| match self.wires.iter().position(|&w| w == c) {
+                Some(wire) => Ok(mask | 1 << wire),
+                None => Err(DecodeError::UnknownWire(c)),
+            })
+    }
 
-pub fn find_value(segment: &str, combination: &str) -> usize {
-    let valid_segments = vec![
-        "abcefg", "cf", "acdeg", "acdfg", "bcdf", "abdfg", "abdefg", "acf", "abcdefg", "abcdfg",
-    ];
+    // pattern lengths that identify a digit without knowing the wiring
+    pub fn unique_lengths(&self) -> Vec<u32> {
+        let mut lengths: Vec<u32> = self.digits.iter().map(|d| d.count_ones()).collect();
+        lengths.sort_unstable();
+        lengths
+            .iter()
+            .filter(|&&len| lengths.iter().filter(|&&l| l == len).count() == 1)
+            .copied()
+            .collect()
+    }
 
-    return valid_segments
-        .iter()
-        .position(|&s| same_chars(segment, &map_segment(s, combination)))
-        .unwrap();
-}
+    pub fn decode_line(&self, line: &str) -> Result<Vec<usize>, DecodeError> {
+        let (signals, outputs) = line.split_once('|').ok_or(DecodeError::MissingSeparator)?;
+        let signals = self.parse_patterns(signals)?;
+        let outputs = self.parse_patterns(outputs)?;
+        self.decode(&signals, &outputs)
+    }
 
-fn same_chars(a: &str, b: &str) -> bool {
-    if a.len() != b.len() {
-        return false;
+    fn parse_patterns(&self, patterns: &str) -> Result<Vec<u32>, DecodeError> {
+        patterns
+            .split_whitespace()
+            .map(|p| self.parse_pattern(p))
+            .collect()
     }
-    let set: HashSet<char> = a.chars().collect();
 
-    b.chars().all(|c| set.contains(&c))
-}
+    // every observed pattern must map onto a distinct digit; the output is only
+    // returned if all wirings consistent with the observations read it the same way
+    pub fn decode(&self, signals: &[u32], outputs: &[u32]) -> Result<Vec<usize>, DecodeError> {
+        let all = self.all_segments();
+        if let Some(&invalid) = signals
+            .iter()
+            .chain(outputs)
+            .find(|&&p| p == 0 || p & !all != 0)
+        {
+            return Err(DecodeError::InvalidPattern(invalid));
+        }
+
+        // outputs go first, so a reading is fixed before the remaining signals are checked
+        let mut patterns: Vec<u32> = outputs.to_vec();
+        self.sort_patterns(&mut patterns);
+        let output_count = patterns.len();
+        let mut rest: Vec<u32> = signals
+            .iter()
+            .filter(|p| !patterns.contains(p))
+            .copied()
+            .collect();
+        self.sort_patterns(&mut rest);
+        patterns.extend(rest);
+
+        let mut search = AssignmentSearch {
+            display: self,
+            patterns: &patterns,
+            output_count,
+            digits: vec![],
+            used: vec![false; self.digits.len()],
+            assignments: vec![],
+        };
+        search.read(&[(all, all)]);
+
+        let mut readings = search.assignments.iter().map(|assignment| {
+            outputs
+                .iter()
+                .map(|o| assignment[patterns.iter().position(|p| p == o).unwrap()])
+                .collect::<Vec<usize>>()
+        });
+        match (readings.next(), readings.next()) {
+            (None, _) => Err(DecodeError::Contradictory),
+            (Some(reading), None) => Ok(reading),
+            (Some(first), Some(second)) => Err(DecodeError::Ambiguous(first, second)),
+        }
+    }
 
-pub fn find_encoding(segments: &[String]) -> String {
-    let signals = "abcdefg".to_string();
-    for combination in signals
-        .chars()
-        .permutations(signals.len())
-        .map(|s| s.iter().collect::<String>())
-    {
-        if all_segments_match(segments, &combination) {
-            println!("Found combination! {}", combination);
-            return combination;
+    // distinct patterns, the ones with the fewest digits of the same length first
+    fn sort_patterns(&self, patterns: &mut Vec<u32>) {
+        patterns.sort_unstable();
+        patterns.dedup();
+        patterns.sort_by_key(|&p| self.same_length(p).count());
+    }
+
+    fn same_length(&self, pattern: u32) -> impl Iterator<Item = usize> + '_ {
+        (0..self.digits.len()).filter(move |&d| self.digits[d].count_ones() == pattern.count_ones())
+    }
+
+    fn all_segments(&self) -> u32 {
+        match self.wires.len() {
+            32 => u32::MAX,
+            len => (1 << len) - 1,
         }
     }
-    panic!("Did not found combination")
 }
 
-pub fn all_segments_match(segments: &[String], combination: &str) -> bool {
-    let valid_segments: Vec<_> = vec![
-        "abcefg", "cf", "acdeg", "acdfg", "bcdf", "abdfg", "abdefg", "acf", "abcdefg", "abcdfg",
-    ];
+// a wiring exists iff every class of wires, split by the patterns they light up, has as many
+// wires as the class of segments split by the assigned digits
+struct AssignmentSearch<'a> {
+    display: &'a SegmentDisplay,
+    patterns: &'a [u32],
+    output_count: usize,
+    digits: Vec<usize>,
+    used: Vec<bool>,
+    assignments: Vec<Vec<usize>>,
+}
 
-    valid_segments
-        .iter()
-        .all(|s| segment_matches(s, combination, segments))
+impl<'a> AssignmentSearch<'a> {
+    // every assignment of the output patterns is kept once some wiring explains all patterns
+    fn read(&mut self, classes: &[(u32, u32)]) {
+        if self.digits.len() == self.output_count {
+            if self.complete(classes) {
+                self.assignments.push(self.digits.clone());
+            }
+            return;
+        }
+
+        for digit in self.candidates() {
+            if let Some(refined) = self.refine(classes, digit) {
+                self.push(digit);
+                self.read(&refined);
+                self.pop();
+                if self.assignments.len() > 1 {
+                    return;
+                }
+            }
+        }
+    }
+
+    fn complete(&mut self, classes: &[(u32, u32)]) -> bool {
+        if self.digits.len() == self.patterns.len() {
+            return true;
+        }
+
+        for digit in self.candidates() {
+            if let Some(refined) = self.refine(classes, digit) {
+                self.push(digit);
+                let found = self.complete(&refined);
+                self.pop();
+                if found {
+                    return true;
+                }
+            }
+        }
+        false
+    }
+
+    fn candidates(&self) -> Vec<usize> {
+        let pattern = self.patterns[self.digits.len()];
+        self.display
+            .same_length(pattern)
+            .filter(|&d| !self.used[d])
+            .collect()
+    }
+
+    // splits every (wires, segments) class by the next pattern and the digit it is assigned
+    fn refine(&self, classes: &[(u32, u32)], digit: usize) -> Option<Vec<(u32, u32)>> {
+        let pattern = self.patterns[self.digits.len()];
+        let segments = self.display.digits[digit];
+        let mut refined = Vec::with_capacity(classes.len() * 2);
+        for &(wire_class, segment_class) in classes {
+            let lit = (wire_class & pattern, segment_class & segments);
+            if lit.0.count_ones() != lit.1.count_ones() {
+                return None;
+            }
+            let unlit = (wire_class & !pattern, segment_class & !segments);
+            refined.extend([lit, unlit].iter().filter(|(wires, _)| *wires != 0));
+        }
+        Some(refined)
+    }
+
+    fn push(&mut self, digit: usize) {
+        self.used[digit] = true;
+        self.digits.push(digit);
+    }
+
+    fn pop(&mut self) {
+        let digit = self.digits.pop().unwrap();
+        self.used[digit] = false;
+    }
 }
 
-pub fn segment_matches(valid_segment: &str, combination: &str, signals: &[String]) -> bool {
-    let mapped = map_segment(valid_segment, combination);
+pub fn part_1(input: &[String]) -> usize {
+    let display = SegmentDisplay::seven_segment();
+    let unique_lengths = display.unique_lengths();
+    input
+        .iter()
+        .filter_map(|l| l.split_once('|'))
+        .flat_map(|(_, outputs)| outputs.split_whitespace())
+        .filter(|t| unique_lengths.contains(&(t.len() as u32)))
+        .count()
+}
 
-    return signals.iter().any(|s| same_chars(s, &mapped));
+pub fn part_2(input: &[String]) -> Result<usize, DecodeError> {
+    let display = SegmentDisplay::seven_segment();
+    input.iter().try_fold(0usize, |sum, line| {
+        let value = get_line_value(&display, line)?;
+        sum.checked_add(value).ok_or(DecodeError::Overflow)
+    })
 }
 
-pub fn map_segment(valid_segment: &str, combination: &str) -> String {
-    valid_segment
-        .chars()
-        .map(|s| map_signal(s, combination))
-        .collect()
+pub fn from_digits(digits: &[usize], base: usize) -> Option<usize> {
+    digits
+        .iter()
+        .try_fold(0usize, |acc, &d| acc.checked_mul(base)?.checked_add(d))
 }
 
-fn map_signal(c: char, combination: &str) -> char {
-    let index = (c as u32 - 96 - 1) as usize;
-    combination.chars().nth(index).unwrap()
+pub fn get_line_value(display: &SegmentDisplay, line: &str) -> Result<usize, DecodeError> {
+    let digits = display.decode_line(line)?;
+    from_digits(&digits, display.base()).ok_or(DecodeError::Overflow)
 }
 
 #[cfg(test)]
@@ -127,84 +299,165 @@ mod tests {
 
     use super::*;
 
+    fn example() -> Vec<String> {
+        vec_of_strings![
+            "be cfbegad cbdgef fgaecd cgeb fdcge agebfd fecdb fabcd edb | fdgacbe cefdb cefbgd gcbe",
+            "edbfga begcd cbg gc gcadebf fbgde acbgfd abcde gfcbed gfec | fcgedb cgb dgebacf gc",
+            "fgaebd cg bdaec gdafb agbcfd gdcbef bgcad gfac gcb cdgabef | cg cg fdcagb cbg",
+            "fbegcd cbd adcefb dageb afcb bc aefdc ecdab fgdeca fcdbega | efabcd cedba gadfec cb",
+            "aecbfdg fbg gf bafeg dbefa fcge gcbea fcaegb dgceab fcbdga | gecf egdcabf bgf bfgea",
+            "fgeab ca afcebg bdacfeg cfaedg gcfdb baec bfadeg bafgc acf | gebdcfa ecba ca fadegcb",
+            "dbcfg fgd bdegcaf fgec aegbdf ecdfab fbedc dacgb gdcebf gf | cefg dcbef fcge gbcadfe",
+            "bdfegc cbegaf gecbf dfcage bdacg ed bedf ced adcbefg gebcd | ed bcgafe cdgba cbgef",
+            "egadfb cdbfeg cegd fecab cgb gbdefca cg fgcdab egfdb bfceg | gbdfcae bgc cg cgb",
+            "gcafb gcf dcaebfg ecagb gf abcdeg gaef cafbge fdbac fegbdc | fgae cfgab fg bagce"
+        ]
+    }
+
+    #[test]
+    fn test_part_1() {
+        assert_eq!(part_1(&example()), 26);
+    }
+
+    #[test]
+    fn test_part_2() {
+        assert_eq!(part_2(&example()), Ok(61229));
+    }
+
+    #[test]
+    fn test_get_line_value() {
+        // given
+        let line =
+            "acedgfb cdfbe gcdfa fbcad dab cefabd cdfgeb eafb cagedb ab | cdfeb fcadb cdfeb cdbaf";
+
+        // when
+        let result = get_line_value(&SegmentDisplay::seven_segment(), line);
+
+        // then
+        assert_eq!(result, Ok(5353));
+    }
+
+    #[test]
+    fn test_unique_lengths() {
+        assert_eq!(
+            SegmentDisplay::seven_segment().unique_lengths(),
+            vec![2, 3, 4, 7]
+        );
+    }
+
     #[test]
-    fn test_map_segment_1() {
+    fn test_decode_partial_observations() {
         // given
-        let segment = "cf";
-        let combination = "deafgbc";
+        let display = SegmentDisplay::seven_segment();
 
         // when
-        let result = map_segment(segment, combination);
+        let one = display.decode_line("gd | dg gd");
+        let four_and_one = display.decode_line("ab abcd | abdc");
 
         // then
-        assert_eq!(result, "ab");
+        assert_eq!(one, Ok(vec![1, 1]));
+        assert_eq!(four_and_one, Ok(vec![4]));
     }
 
     #[test]
-    fn test_map_segment_2() {
+    fn test_decode_errors() {
         // given
-        let segment = "bcdf";
-        let combination = "deafgbc";
+        let display = SegmentDisplay::seven_segment();
 
         // when
-        let result = map_segment(segment, combination);
+        let missing = display.decode_line("ab cf");
+        let unknown = display.decode_line("ab | abz");
+        let contradictory = display.decode_line("ab abc abd | ab");
+        let ambiguous = display.decode_line("abcdf | abcdf");
 
         // then
-        assert_eq!(result, "eafb");
+        assert_eq!(missing, Err(DecodeError::MissingSeparator));
+        assert_eq!(unknown, Err(DecodeError::UnknownWire('z')));
+        assert_eq!(contradictory, Err(DecodeError::Contradictory));
+        assert!(matches!(ambiguous, Err(DecodeError::Ambiguous(_, _))));
     }
 
     #[test]
-    fn test_segment_matches() {
+    fn test_decode_rejects_invalid_masks() {
         // given
-        let segments = vec_of_strings![
-            "acedgfb", "cdfbe", "gcdfa", "fbcad", "dab", "cefabd", "cdfgeb", "eafb", "cagedb", "ab"
-        ];
-        let combination = "deafgbc";
+        let display = SegmentDisplay::seven_segment();
 
         // when
-        let result = all_segments_match(&segments, combination);
+        let empty = display.decode(&[0b11], &[0]);
+        let unknown_wire = display.decode(&[0b11], &[1 << 7]);
 
         // then
-        assert_eq!(result, true);
+        assert_eq!(empty, Err(DecodeError::InvalidPattern(0)));
+        assert_eq!(unknown_wire, Err(DecodeError::InvalidPattern(1 << 7)));
     }
 
     #[test]
-    fn test_find_encoding_1() {
+    fn test_custom_alphabet() {
         // given
-        let segments = vec_of_strings![
-            "acedgfb", "cdfbe", "gcdfa", "fbcad", "dab", "cefabd", "cdfgeb", "eafb", "cagedb", "ab"
-        ];
+        let display = SegmentDisplay::new("abcd", &["a", "ab", "abc", "bcd"]);
 
         // when
-        let result = find_encoding(&segments);
+        let result = get_line_value(&display, "d db dbc bca | bca db");
 
         // then
-        assert_eq!(result, "deafgbc");
+        assert_eq!(result, Ok(13));
     }
 
     #[test]
-    fn test_decode_1() {
+    fn test_sixteen_segments_partial_observations() {
         // given
-        let segments = vec_of_strings!["cdfeb", "fcadb", "cdfeb", "cdbaf"];
-        let combination = "deafgbc";
+        let wires = "abcdefghijklmnop";
+        let digits: Vec<&str> = (1..=wires.len()).map(|len| &wires[..len]).collect();
+        let display = SegmentDisplay::new(wires, &digits);
 
         // when
-        let result = decode(&segments, combination);
+        let full = display.decode_line("ponmlkjihgfedcba | ponmlkjihgfedcba");
+        let partial = display.decode_line("mp ponm | ponm mp");
 
         // then
-        assert_eq!(result, 5353);
+        assert_eq!(full, Ok(vec![15]));
+        assert_eq!(partial, Ok(vec![3, 1]));
     }
 
     #[test]
-    fn test_get_line_value() {
+    fn test_long_output_overflows() {
         // given
-        let line =
-            "acedgfb cdfbe gcdfa fbcad dab cefabd cdfgeb eafb cagedb ab | cdfeb fcadb cdfeb cdbaf";
+        let wires = "abcdefghijklmnop";
+        let digits: Vec<&str> = (1..=wires.len()).map(|len| &wires[..len]).collect();
+        let display = SegmentDisplay::new(wires, &digits);
+        let line = format!("ab | {}", vec!["ba"; 17].join(" "));
+
+        // when
+        let result = get_line_value(&display, &line);
+
+        // then
+        assert_eq!(from_digits(&[1; 17], 16), None);
+        assert_eq!(result, Err(DecodeError::Overflow));
+    }
+
+    #[test]
+    fn test_hex_display() {
+        // given
+        let mut digits = SEVEN_SEGMENT_DIGITS.to_vec();
+        digits.extend(["abcdef", "bdefg", "abeg", "cdefg", "abdeg", "abde"]);
+        let display = SegmentDisplay::new("abcdefg", &digits);
+        let scramble = |pattern: &str| -> String {
+            pattern
+                .chars()
+                .map(|c| "gfaebdc".chars().nth(c as usize - 'a' as usize).unwrap())
+                .collect()
+        };
+        let signals: Vec<String> = digits.iter().map(|d| scramble(d)).collect();
+        let outputs: Vec<String> = [15, 0, 12, 3]
+            .iter()
+            .map(|&d| scramble(digits[d]))
+            .collect();
+        let line = format!("{} | {}", signals.join(" "), outputs.join(" "));
 
         // when
-        let result = get_line_value(line);
+        let result = get_line_value(&display, &line);
 
         // then
-        assert_eq!(result, 5353);
+        assert_eq!(result, Ok(0xf0c3));
     }
 }
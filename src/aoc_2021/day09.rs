@@ -1,5 +1,5 @@
 use itertools::Itertools;
-use std::collections::HashSet;
+use std::collections::{HashSet, VecDeque};
 
 pub fn run(input: &[String]) {
     let heightmap = parse_heightmap(input);
@@ -7,9 +7,42 @@ pub fn run(input: &[String]) {
     println!("Heightmap risk is: {}", risk);
     let product = heightmap.largest_basins_product(3);
     println!("Largest basins product is: {}", product);
+
+    let labels = heightmap.label_basins();
+    let unlabelled = labels
+        .grid()
+        .iter()
+        .flatten()
+        .filter(|l| l.is_none())
+        .count();
+    println!(
+        "Found {} basins, {} cells outside any basin",
+        labels.basin_count(),
+        unlabelled
+    );
+    if let Some(largest) = (0..labels.basin_count()).max_by_key(|&id| labels.size(id)) {
+        println!(
+            "Largest basin {} has low point {:?}, size {} and perimeter {}",
+            largest,
+            labels.low_point(largest),
+            labels.size(largest),
+            labels.perimeter(largest)
+        );
+    }
+    let (row, column) = (heightmap.height / 2, heightmap.width / 2);
+    println!(
+        "Basin at ({}, {}): {:?}",
+        row,
+        column,
+        labels.basin_at(row, column)
+    );
+    println!("{}", heightmap.render_basins(&labels));
 }
 
-struct HeightMap {
+const BASIN_WALL: i32 = 9;
+const RESET: &str = "\x1b[0m";
+
+pub struct HeightMap {
     width: i32,
     height: i32,
     values: Vec<i32>,
@@ -80,7 +113,7 @@ impl HeightMap {
                 let index = self.get_index(r, c);
                 match self.values.get(index) {
                     None => false,
-                    Some(value) => *value != 9,
+                    Some(value) => *value != BASIN_WALL,
                 }
             })
             .collect()
@@ -151,6 +184,156 @@ impl HeightMap {
             .unique()
             .collect()
     }
+
+    pub fn label_basins(&self) -> BasinLabels {
+        let mut labels: Vec<Option<usize>> = vec![None; self.values.len()];
+        let mut low_points = vec![];
+        let mut sizes = vec![];
+
+        for low_point in self.find_low_points() {
+            if labels[self.get_index(low_point.0, low_point.1)].is_some() {
+                continue;
+            }
+            let (size, _) = self.flood_basin(low_point, low_points.len(), &mut labels);
+            low_points.push(low_point);
+            sizes.push(size);
+        }
+
+        // flat basins have no strict low point, so any cell left unlabelled seeds one
+        for seed in (0..self.height).cartesian_product(0..self.width) {
+            let index = self.get_index(seed.0, seed.1);
+            if labels[index].is_some() || self.values[index] == BASIN_WALL {
+                continue;
+            }
+            let (size, lowest) = self.flood_basin(seed, low_points.len(), &mut labels);
+            low_points.push(lowest);
+            sizes.push(size);
+        }
+
+        let mut perimeters = vec![0; low_points.len()];
+        for (r, c) in (0..self.height).cartesian_product(0..self.width) {
+            if let Some(id) = labels[self.get_index(r, c)] {
+                let inner = self
+                    .neighbours(r, c)
+                    .iter()
+                    .filter(|&&(nr, nc)| labels[self.get_index(nr, nc)] == Some(id))
+                    .count();
+                perimeters[id] += 4 - inner;
+            }
+        }
+
+        BasinLabels {
+            width: self.width,
+            height: self.height,
+            labels,
+            low_points,
+            sizes,
+            perimeters,
+        }
+    }
+
+    // returns the basin size and its lowest cell, the first one reached on ties
+    fn flood_basin(
+        &self,
+        seed: (i32, i32),
+        id: usize,
+        labels: &mut [Option<usize>],
+    ) -> (usize, (i32, i32)) {
+        let mut size = 0;
+        let mut lowest = seed;
+        let mut queue = VecDeque::from([seed]);
+        labels[self.get_index(seed.0, seed.1)] = Some(id);
+        while let Some((r, c)) = queue.pop_front() {
+            size += 1;
+            if self.get_value(r, c) < self.get_value(lowest.0, lowest.1) {
+                lowest = (r, c);
+            }
+            for (nr, nc) in self.neighbours(r, c) {
+                let index = self.get_index(nr, nc);
+                if labels[index].is_none() && self.values[index] != BASIN_WALL {
+                    labels[index] = Some(id);
+                    queue.push_back((nr, nc));
+                }
+            }
+        }
+        (size, lowest)
+    }
+
+    pub fn render_basins(&self, labels: &BasinLabels) -> String {
+        (0..self.height)
+            .map(|r| {
+                (0..self.width)
+                    .map(|c| {
+                        let value = self.get_value(r, c);
+                        match labels.basin_at(r, c) {
+                            Some(id) => format!("{}{}{}", basin_colour(id), value, RESET),
+                            None => value.to_string(),
+                        }
+                    })
+                    .collect::<String>()
+            })
+            .join("\n")
+    }
+
+    fn neighbours(&self, row: i32, column: i32) -> Vec<(i32, i32)> {
+        [(0, 1), (0, -1), (-1, 0), (1, 0)]
+            .iter()
+            .map(|(dr, dc)| (row + dr, column + dc))
+            .filter(|&(r, c)| r >= 0 && c >= 0 && r < self.height && c < self.width)
+            .collect()
+    }
+}
+
+pub struct BasinLabels {
+    width: i32,
+    height: i32,
+    labels: Vec<Option<usize>>,
+    low_points: Vec<(i32, i32)>,
+    sizes: Vec<usize>,
+    perimeters: Vec<usize>,
+}
+
+impl BasinLabels {
+    pub fn basin_count(&self) -> usize {
+        self.low_points.len()
+    }
+
+    pub fn basin_at(&self, row: i32, column: i32) -> Option<usize> {
+        if row < 0 || column < 0 || row >= self.height || column >= self.width {
+            return None;
+        }
+        self.labels[(row * self.width + column) as usize]
+    }
+
+    pub fn low_point(&self, basin: usize) -> (i32, i32) {
+        self.low_points[basin]
+    }
+
+    pub fn size(&self, basin: usize) -> usize {
+        self.sizes[basin]
+    }
+
+    pub fn perimeter(&self, basin: usize) -> usize {
+        self.perimeters[basin]
+    }
+
+    pub fn grid(&self) -> Vec<Vec<Option<usize>>> {
+        if self.width == 0 {
+            return vec![];
+        }
+        self.labels
+            .chunks(self.width as usize)
+            .map(|row| row.to_vec())
+            .collect()
+    }
+}
+
+fn basin_colour(basin: usize) -> String {
+    // skip the greyscale ramp and the darkest cube colours
+    const PALETTE_START: usize = 17;
+    const PALETTE_SIZE: usize = 214;
+    let colour = PALETTE_START + (basin * 37) % PALETTE_SIZE;
+    format!("\x1b[38;5;{}m", colour)
 }
 
 fn parse_heightmap(input: &[String]) -> HeightMap {
@@ -450,4 +633,108 @@ mod tests {
 
         assert_eq!(result, 1134);
     }
+
+    #[test]
+    fn test_label_basins() {
+        // given
+        let input = vec_of_strings![
+            "2199943210",
+            "3987894921",
+            "9856789892",
+            "8767896789",
+            "9899965678"
+        ];
+        let heightmap = parse_heightmap(&input);
+
+        // when
+        let labels = heightmap.label_basins();
+
+        // then
+        assert_eq!(labels.basin_count(), 4);
+        let sizes: Vec<usize> = (0..4).map(|id| labels.size(id)).collect();
+        assert_eq!(sizes, vec![3, 9, 14, 9]);
+        let low_points: Vec<(i32, i32)> = (0..4).map(|id| labels.low_point(id)).collect();
+        assert_eq!(low_points, vec![(0, 1), (0, 9), (2, 2), (4, 6)]);
+        assert_eq!(labels.basin_at(1, 0), Some(0));
+        assert_eq!(labels.basin_at(0, 2), None);
+        assert_eq!(labels.basin_at(-1, 0), None);
+        let sizes_total: usize = sizes.iter().sum();
+        let labelled = labels
+            .grid()
+            .iter()
+            .flatten()
+            .filter(|l| l.is_some())
+            .count();
+        assert_eq!(labelled, sizes_total);
+    }
+
+    #[test]
+    fn test_basin_perimeters() {
+        // given
+        let input = vec_of_strings![
+            "2199943210",
+            "3987894921",
+            "9856789892",
+            "8767896789",
+            "9899965678"
+        ];
+        let heightmap = parse_heightmap(&input);
+
+        // when
+        let labels = heightmap.label_basins();
+
+        // then
+        assert_eq!(labels.perimeter(0), 8);
+        assert_eq!(labels.perimeter(1), 18);
+    }
+
+    #[test]
+    fn test_render_basins() {
+        // given
+        let input = vec_of_strings!["19", "91"];
+        let heightmap = parse_heightmap(&input);
+        let labels = heightmap.label_basins();
+
+        // when
+        let result = heightmap.render_basins(&labels);
+
+        // then
+        let first = basin_colour(0);
+        let second = basin_colour(1);
+        assert_ne!(first, second);
+        let expected = format!("{}1{}9\n9{}1{}", first, RESET, second, RESET);
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn test_label_plateau_basin() {
+        // given
+        let input = vec_of_strings!["5519", "5593", "9999", "4449"];
+        let heightmap = parse_heightmap(&input);
+
+        // when
+        let labels = heightmap.label_basins();
+
+        // then
+        assert_eq!(labels.basin_count(), 3);
+        let sizes: Vec<usize> = (0..3).map(|id| labels.size(id)).collect();
+        assert_eq!(sizes, vec![5, 1, 3]);
+        let low_points: Vec<(i32, i32)> = (0..3).map(|id| labels.low_point(id)).collect();
+        assert_eq!(low_points, vec![(0, 2), (1, 3), (3, 0)]);
+        assert_eq!(labels.basin_at(3, 1), Some(2));
+        assert!(heightmap.render_basins(&labels).contains(&basin_colour(2)));
+    }
+
+    #[test]
+    fn test_label_empty_heightmap() {
+        // given
+        let heightmap = HeightMap::new(vec![], 0, 0);
+
+        // when
+        let labels = heightmap.label_basins();
+
+        // then
+        assert_eq!(labels.basin_count(), 0);
+        assert!(labels.grid().is_empty());
+    }
 }
@@ -1,142 +1,56 @@
-use std::collections::HashSet;
-
-use itertools::Itertools;
-
-struct OctopodesMap {
-    width: i32,
-    height: i32,
-    values: Vec<i32>,
+use crate::common::automaton::{
+    Automaton, Moore, Neighbourhood, StepEvent, ThresholdRule, VonNeumann,
+};
+
+const OCTOPUS_RULE: ThresholdRule = ThresholdRule {
+    increment: 1,
+    threshold: 9,
+    reset_value: 0,
+};
+
+struct OctopodesMap<N: Neighbourhood> {
+    automaton: Automaton<ThresholdRule, N>,
 }
 
-impl OctopodesMap {
-    pub fn new(values: Vec<i32>, width: i32, height: i32) -> OctopodesMap {
+impl<N: Neighbourhood> OctopodesMap<N> {
+    pub fn new(values: Vec<i32>, width: i32, height: i32, neighbourhood: N) -> OctopodesMap<N> {
         OctopodesMap {
-            width,
-            height,
-            values,
+            automaton: Automaton::new(values, width, height, neighbourhood, OCTOPUS_RULE),
         }
     }
 
-    fn get_index(&self, row: i32, column: i32) -> usize {
-        (row * self.width + column) as usize
-    }
-
-    fn get_value(&self, row: i32, column: i32) -> i32 {
-        self.values[self.get_index(row, column)]
-    }
-
-    fn increase_energy(&mut self, value: i32) {
-        for v in self.values.iter_mut() {
-            *v += value
-        }
-    }
-
-    fn increase_for_points(&mut self, points: &[(i32, i32)], value: usize) {
-        for &(r, c) in points.iter() {
-            let index = self.get_index(r, c);
-            *self.values.get_mut(index).unwrap() += value as i32
-        }
-    }
-
-    fn reset_points(&mut self, points: &HashSet<(i32, i32)>) {
-        for &(r, c) in points.iter() {
-            let index = self.get_index(r, c);
-            *self.values.get_mut(index).unwrap() = 0
-        }
-    }
-
-    fn get_flashing(&self, flashed: &HashSet<(i32, i32)>) -> HashSet<(i32, i32)> {
-        (0..self.height)
-            .cartesian_product(0..self.width)
-            .filter(|&(row, column)| {
-                self.get_value(row, column) > 9 && !flashed.contains(&(row, column))
-            })
-            .collect()
-    }
-
-    fn get_adjacent(&self, row: i32, column: i32) -> Vec<(i32, i32)> {
-        let coords = vec![
-            (-1, -1),
-            (-1, 0),
-            (-1, 1),
-            (0, -1),
-            (0, 1),
-            (1, -1),
-            (1, 0),
-            (1, 1),
-        ];
-
-        coords
-            .iter()
-            .map(|(dr, dc)| (row + dr, column + dc))
-            .collect()
-    }
-
     fn count_flashes(&mut self, num_steps: usize) -> usize {
         (0..num_steps).map(|_| self.step()).sum::<usize>()
     }
 
     fn first_simultaneous(&mut self) -> usize {
-        let mut loop_count = 0;
-        loop {
-            loop_count += 1;
-            let step_count = self.step();
-            if step_count == self.values.len() {
-                return loop_count;
-            }
-        }
+        let size = self.automaton.cells().len();
+        self.automaton
+            .steps()
+            .find(|event| event.fired.len() == size)
+            .map(|event| event.step)
+            .unwrap()
     }
 
     fn step(&mut self) -> usize {
-        // increase total energy by 1
-        self.increase_energy(1);
-        let mut all_flashed: HashSet<(i32, i32)> = HashSet::new();
-        loop {
-            let flashed = self.get_flashing(&all_flashed);
-            if flashed.is_empty() {
-                break;
-            }
-            all_flashed.extend(flashed.iter());
-            // get all valid adjacent points adjacent points that are not
-            // out of bounds, and not already flashed. Some points will be duplicated
-            // when they are adjacent to multiple flashing
-            let adjacent = self.get_all_valid_adjacent(&flashed, &all_flashed);
-            // increase value for adjacent
-            self.increase_for_points(&adjacent, 1);
-        }
-        self.reset_points(&all_flashed);
-        all_flashed.len()
+        self.step_events().fired.len()
     }
 
-    fn get_all_valid_adjacent(
-        &self,
-        points: &HashSet<(i32, i32)>,
-        flashed: &HashSet<(i32, i32)>,
-    ) -> Vec<(i32, i32)> {
-        points
-            .iter()
-            .flat_map(|&(r, c)| self.get_adjacent(r, c))
-            .filter(|p| !self.out_of_bounds(p.0, p.1) && !flashed.contains(p))
-            .collect()
+    fn step_events(&mut self) -> StepEvent {
+        self.automaton.step()
     }
+}
 
-    fn out_of_bounds(&self, row: i32, column: i32) -> bool {
-        if row < 0 || row >= self.height {
-            return true;
-        }
-        if column < 0 || column >= self.width {
-            return true;
-        }
-        false
-    }
+fn parse_octopodes(input: &[String]) -> OctopodesMap<Moore> {
+    parse_octopodes_with(input, Moore)
 }
 
-fn parse_octopodes(input: &[String]) -> OctopodesMap {
+fn parse_octopodes_with<N: Neighbourhood>(input: &[String], neighbourhood: N) -> OctopodesMap<N> {
     let width = parse_line(&input[0]).len() as i32;
     let height = input.len() as i32;
     let values: Vec<i32> = input.iter().flat_map(|l| parse_line(l)).collect();
 
-    OctopodesMap::new(values, width, height)
+    OctopodesMap::new(values, width, height, neighbourhood)
 }
 
 fn parse_line(line: &str) -> Vec<i32> {
@@ -151,7 +65,22 @@ pub fn run(input: &[String]) {
     println!("Flash count: {}", count);
 
     let step = first_simultaneous(input);
-    println!("First simultaneous flash at step: {}", step)
+    println!("First simultaneous flash at step: {}", step);
+
+    let mut opd = parse_octopodes(input);
+    let event = opd.step_events();
+    println!(
+        "Step {} flashed {} octopodes, first at {:?}",
+        event.step,
+        event.fired.len(),
+        event.fired.first()
+    );
+
+    let mut orthogonal = parse_octopodes_with(input, VonNeumann);
+    println!(
+        "Flash count with orthogonal neighbours only: {}",
+        orthogonal.count_flashes(100)
+    );
 }
 
 pub fn flash_count(input: &[String]) -> usize {
@@ -171,18 +100,16 @@ mod tests {
     use super::*;
 
     #[test]
-    fn test_get_flashing() {
+    fn test_step_events() {
         // given
         let input = vec_of_strings!["11111", "19991", "19191", "19991", "11111"];
         let mut opd = parse_octopodes(&input);
-        let flashed: HashSet<(i32, i32)> = HashSet::new();
 
         // when
-        opd.increase_energy(1);
-        let flashing = opd.get_flashing(&flashed);
+        let event = opd.step_events();
 
         // then
-        let expected = HashSet::from([
+        let ring = [
             (1, 1),
             (1, 2),
             (1, 3),
@@ -191,9 +118,23 @@ mod tests {
             (3, 1),
             (3, 2),
             (3, 3),
-        ]);
-        assert_eq!(flashing.len(), 8);
-        assert_eq!(flashing, expected);
+        ];
+        assert_eq!(event.step, 1);
+        assert_eq!(event.fired[..8], ring[..]);
+        assert_eq!(event.fired[8], (2, 2));
+    }
+
+    #[test]
+    fn test_count_flashes_von_neumann() {
+        // given
+        let input = vec_of_strings!["11111", "19991", "19191", "19991", "11111"];
+        let mut opd = parse_octopodes_with(&input, VonNeumann);
+
+        // when
+        let count = opd.step();
+
+        // then
+        assert_eq!(count, 8);
     }
 
     #[test]
@@ -5,7 +5,20 @@ pub fn run(input: &[String]) {
     println!("Path count: {}", count);
 
     let count_2 = count_paths_2(input);
-    println!("Second path count: {}", count_2)
+    println!("Second path count: {}", count_2);
+
+    let cave = parse_cave(input);
+    let policy = RepeatLimit {
+        repeated_caves: 2,
+        max_visits: 3,
+    };
+    println!(
+        "Path count with two small caves visited up to three times: {}",
+        cave.count_paths_with(&policy)
+    );
+    if let Some(path) = cave.paths(&RepeatLimit::one_twice()).next() {
+        println!("First path: {}", path.join(","));
+    }
 }
 
 #[derive(Debug)]
@@ -16,137 +29,226 @@ pub struct Distance {
 
 #[derive(Debug)]
 pub struct CaveMatrix {
-    neighbours: HashMap<String, Vec<String>>,
+    names: Vec<String>,
+    neighbours: Vec<Vec<usize>>,
+    small_ids: Vec<Option<usize>>,
+    small_count: usize,
+    start: usize,
+    end: usize,
 }
 
-#[derive(Debug, Clone)]
-pub struct CavePath {
-    caves: Vec<String>,
-    small_twice_visited: bool,
-    visits: HashMap<String, usize>,
+pub trait VisitPolicy {
+    fn can_visit(&self, visits: &[u8], small_cave: usize) -> bool;
 }
 
-impl CavePath {
-    fn new() -> CavePath {
-        CavePath {
-            small_twice_visited: false,
-            caves: vec![],
-            visits: HashMap::new(),
-        }
-    }
+pub struct RepeatLimit {
+    repeated_caves: usize,
+    max_visits: u8,
+}
 
-    fn contains(&self, cave: &str) -> bool {
-        self.caves.contains(&cave.to_string())
-    }
+pub struct CavePaths<'a, P: VisitPolicy> {
+    matrix: &'a CaveMatrix,
+    policy: &'a P,
+    stack: Vec<(usize, usize)>,
+    visits: Vec<u8>,
+}
 
-    fn push(&mut self, cave: &str) {
-        self.caves.push(cave.to_string());
-        self.visits.insert(
-            cave.to_string(),
-            1 + if self.visits.contains_key(cave) {
-                self.visits[cave]
-            } else {
-                0
-            },
-        );
-        if !self.small_twice_visited && is_small_cave(cave) {
-            let count = self.count_visits(cave);
-            if count == 2 {
-                self.small_twice_visited = true;
-            }
+impl RepeatLimit {
+    pub fn once_each() -> RepeatLimit {
+        RepeatLimit {
+            repeated_caves: 0,
+            max_visits: 1,
         }
     }
 
-    fn count_visits(&self, cave: &str) -> usize {
-        *self.visits.get(cave).unwrap_or(&0)
-    }
-
-    fn can_push(&self, cave: &str) -> bool {
-        if !is_small_cave(cave) {
-            return true;
+    pub fn one_twice() -> RepeatLimit {
+        RepeatLimit {
+            repeated_caves: 1,
+            max_visits: 2,
         }
+    }
+}
 
-        let cave_visits = self.count_visits(cave);
-        match cave_visits {
+impl VisitPolicy for RepeatLimit {
+    fn can_visit(&self, visits: &[u8], small_cave: usize) -> bool {
+        match visits[small_cave] {
             0 => true,
-            1 => {
-                if cave == "start" || cave == "end" {
-                    return false;
-                }
-
-                !self.small_twice_visited
-            }
-            _ => false,
+            count if count >= self.max_visits => false,
+            1 => visits.iter().filter(|v| **v > 1).count() < self.repeated_caves,
+            _ => true,
         }
     }
 }
 
 impl CaveMatrix {
     fn new(distances: Vec<Distance>) -> CaveMatrix {
-        let mut neighbours: HashMap<String, Vec<String>> = HashMap::new();
-
-        for distance in distances {
-            let from = distance.from.clone();
-            let to = distance.to.clone();
-            if neighbours.contains_key(&from) {
-                let cave = neighbours.get_mut(&from).unwrap();
-                cave.push(to.clone());
-            } else {
-                neighbours.insert(from.clone(), vec![to.clone()]);
-            }
-            if neighbours.contains_key(&to) {
-                let cave = neighbours.get_mut(&to).unwrap();
-                cave.push(from.clone());
-            } else {
-                neighbours.insert(distance.to.clone(), vec![distance.from.clone()]);
+        let mut names: Vec<String> = vec![];
+        let mut neighbours: Vec<Vec<usize>> = vec![];
+        let mut ids: HashMap<String, usize> = HashMap::new();
+
+        let mut id = |name: &str, neighbours: &mut Vec<Vec<usize>>| {
+            *ids.entry(name.to_string()).or_insert_with(|| {
+                names.push(name.to_string());
+                neighbours.push(vec![]);
+                names.len() - 1
+            })
+        };
+
+        for distance in &distances {
+            let from = id(&distance.from, &mut neighbours);
+            let to = id(&distance.to, &mut neighbours);
+            neighbours[from].push(to);
+            neighbours[to].push(from);
+        }
+        let start = id("start", &mut neighbours);
+        let end = id("end", &mut neighbours);
+
+        let mut small_count = 0;
+        let small_ids: Vec<Option<usize>> = names
+            .iter()
+            .map(|name| {
+                is_small_cave(name).then(|| {
+                    small_count += 1;
+                    small_count - 1
+                })
+            })
+            .collect();
+
+        for (cave, next) in neighbours.iter().enumerate() {
+            for other in next {
+                if small_ids[cave].is_none() && small_ids[*other].is_none() {
+                    panic!(
+                        "Big caves {} and {} are connected, path count is infinite",
+                        names[cave], names[*other]
+                    );
+                }
             }
         }
 
-        CaveMatrix { neighbours }
+        CaveMatrix {
+            names,
+            neighbours,
+            small_ids,
+            small_count,
+            start,
+            end,
+        }
     }
 
     fn count_paths(&self) -> usize {
-        let mut curr_path = CavePath::new();
-        let mut all_paths: Vec<CavePath> = vec![];
-        let start = "start";
-        self.dfs(start, &mut curr_path, &mut all_paths);
-        println!("Count!: {}", all_paths.len());
-        all_paths.len()
+        self.count_paths_with(&RepeatLimit::once_each())
     }
 
     fn count_paths_2(&self) -> usize {
-        let mut curr_path = CavePath::new();
-        let mut all_paths: Vec<CavePath> = vec![];
-        let start = "start";
-        self.dfs_2(start, &mut curr_path, &mut all_paths);
-        println!("Count!: {}", all_paths.len());
-        all_paths.len()
+        self.count_paths_with(&RepeatLimit::one_twice())
+    }
+
+    pub fn count_paths_with<P: VisitPolicy>(&self, policy: &P) -> usize {
+        let mut visits = vec![0; self.small_count];
+        let mut memo: HashMap<(usize, Vec<u8>), usize> = HashMap::new();
+        self.enter(self.start, &mut visits);
+        self.count_from(self.start, &mut visits, policy, &mut memo)
     }
 
-    fn dfs(&self, current: &str, path: &mut CavePath, all_paths: &mut Vec<CavePath>) {
-        path.push(current);
+    pub fn paths<'a, P: VisitPolicy>(&'a self, policy: &'a P) -> CavePaths<'a, P> {
+        let mut visits = vec![0; self.small_count];
+        self.enter(self.start, &mut visits);
 
-        if current == "end" {
-            all_paths.push(path.clone());
+        CavePaths {
+            matrix: self,
+            policy,
+            stack: vec![(self.start, 0)],
+            visits,
         }
+    }
 
-        for cave in self.neighbours.get(current).unwrap().iter() {
-            if !(is_small_cave(cave) && path.contains(cave)) {
-                self.dfs(cave, &mut path.clone(), all_paths)
+    fn count_from<P: VisitPolicy>(
+        &self,
+        current: usize,
+        visits: &mut Vec<u8>,
+        policy: &P,
+        memo: &mut HashMap<(usize, Vec<u8>), usize>,
+    ) -> usize {
+        if current == self.end {
+            return 1;
+        }
+
+        let key = (current, visits.clone());
+        if let Some(count) = memo.get(&key) {
+            return *count;
+        }
+
+        let mut count = 0;
+        for next in &self.neighbours[current] {
+            if self.can_enter(*next, visits, policy) {
+                self.enter(*next, visits);
+                count += self.count_from(*next, visits, policy, memo);
+                self.leave(*next, visits);
             }
         }
+
+        memo.insert(key, count);
+        count
+    }
+
+    fn can_enter<P: VisitPolicy>(&self, cave: usize, visits: &[u8], policy: &P) -> bool {
+        match self.small_ids[cave] {
+            _ if cave == self.start => false,
+            Some(small) => policy.can_visit(visits, small),
+            None => true,
+        }
     }
 
-    fn dfs_2(&self, current: &str, path: &mut CavePath, all_paths: &mut Vec<CavePath>) {
-        path.push(current);
+    fn enter(&self, cave: usize, visits: &mut [u8]) {
+        if let Some(small) = self.small_ids[cave] {
+            visits[small] += 1;
+        }
+    }
 
-        if current == "end" {
-            all_paths.push(path.clone());
+    fn leave(&self, cave: usize, visits: &mut [u8]) {
+        if let Some(small) = self.small_ids[cave] {
+            visits[small] -= 1;
         }
+    }
+}
 
-        for cave in self.neighbours.get(current).unwrap().iter() {
-            if path.can_push(cave) {
-                self.dfs_2(cave, &mut path.clone(), all_paths)
+impl<'a, P: VisitPolicy> CavePaths<'a, P> {
+    fn pop(&mut self) {
+        if let Some((cave, _)) = self.stack.pop() {
+            self.matrix.leave(cave, &mut self.visits);
+        }
+    }
+}
+
+impl<'a, P: VisitPolicy> Iterator for CavePaths<'a, P> {
+    type Item = Vec<String>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let matrix = self.matrix;
+
+        loop {
+            let (cave, next_idx) = self.stack.last_mut()?;
+            let cave = *cave;
+
+            if cave == matrix.end {
+                let path = self
+                    .stack
+                    .iter()
+                    .map(|(c, _)| matrix.names[*c].to_string())
+                    .collect();
+                self.pop();
+                return Some(path);
+            }
+
+            match matrix.neighbours[cave].get(*next_idx) {
+                Some(&next) => {
+                    *next_idx += 1;
+                    if matrix.can_enter(next, &self.visits, self.policy) {
+                        matrix.enter(next, &mut self.visits);
+                        self.stack.push((next, 0));
+                    }
+                }
+                None => self.pop(),
             }
         }
     }
@@ -210,4 +312,80 @@ mod tests {
         let cave = parse_cave(&input);
         assert_eq!(cave.count_paths_2(), 36)
     }
+
+    #[test]
+    fn test_count_paths_2_2() {
+        let input = vec_of_strings![
+            "dc-end", "HN-start", "start-kj", "dc-start", "dc-HN", "LN-dc", "HN-end", "kj-sa",
+            "kj-HN", "kj-dc"
+        ];
+        let cave = parse_cave(&input);
+        assert_eq!(cave.count_paths_2(), 103)
+    }
+
+    #[test]
+    fn test_paths_iterator_matches_count() {
+        let input = vec_of_strings!["start-A", "start-b", "A-c", "A-b", "b-d", "A-end", "b-end"];
+        let cave = parse_cave(&input);
+
+        let mut paths: Vec<String> = cave
+            .paths(&RepeatLimit::once_each())
+            .map(|p| p.join(","))
+            .collect();
+        paths.sort();
+
+        let mut expected = vec_of_strings![
+            "start,A,b,A,c,A,end",
+            "start,A,b,A,end",
+            "start,A,b,end",
+            "start,A,c,A,b,A,end",
+            "start,A,c,A,b,end",
+            "start,A,c,A,end",
+            "start,A,end",
+            "start,b,A,c,A,end",
+            "start,b,A,end",
+            "start,b,end"
+        ];
+        expected.sort();
+        assert_eq!(paths, expected);
+
+        let twice = cave.paths(&RepeatLimit::one_twice()).count();
+        assert_eq!(twice, cave.count_paths_2());
+    }
+
+    #[test]
+    fn test_custom_repeat_limit() {
+        let input = vec_of_strings!["start-A", "A-b", "A-c", "A-end"];
+        let cave = parse_cave(&input);
+
+        let policy = RepeatLimit {
+            repeated_caves: 2,
+            max_visits: 3,
+        };
+        let brute_force = cave.paths(&policy).count();
+        assert_eq!(cave.count_paths_with(&policy), brute_force);
+        assert!(cave
+            .paths(&policy)
+            .any(|p| p.join(",") == "start,A,b,A,b,A,b,A,c,A,c,A,c,A,end"));
+        assert!(!cave
+            .paths(&policy)
+            .any(|p| p.iter().filter(|c| *c == "b").count() > 3));
+    }
+
+    #[test]
+    fn test_custom_policy() {
+        struct TotalRevisits(u8);
+        impl VisitPolicy for TotalRevisits {
+            fn can_visit(&self, visits: &[u8], small_cave: usize) -> bool {
+                let revisits: u8 = visits.iter().map(|v| v.saturating_sub(1)).sum();
+                visits[small_cave] == 0 || revisits < self.0
+            }
+        }
+
+        let input = vec_of_strings!["start-A", "A-b", "A-end"];
+        let cave = parse_cave(&input);
+        assert_eq!(cave.count_paths_with(&TotalRevisits(0)), 2);
+        assert_eq!(cave.count_paths_with(&TotalRevisits(1)), 3);
+        assert_eq!(cave.count_paths_with(&TotalRevisits(4)), 6);
+    }
 }
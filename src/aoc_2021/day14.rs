@@ -1,81 +1,124 @@
+use num::{BigUint, Zero};
 use std::collections::BTreeMap;
+use std::ops::AddAssign;
 
 pub fn run(input: &str) {
     println!("Part 1: {}", min_max_diff(input, 10));
     println!("Part 2: {}", min_max_diff(input, 40));
-}
 
-fn char_windows(src: &str, win_size: usize) -> impl Iterator<Item = &str> {
-    src.char_indices().flat_map(move |(from, _)| {
-        src[from..]
-            .char_indices()
-            .nth(win_size - 1)
-            .map(|(to, c)| &src[from..from + to + c.len_utf8()])
-    })
+    let engine = PolymerEngine::parse(input);
+    let histogram = engine.histogram::<BigUint>(100);
+    let length: BigUint = histogram.values().sum();
+    println!("Polymer length after 100 steps: {}", length);
 }
 
-pub fn min_max_diff(input: &str, steps: usize) -> usize {
-    let (polymer, insertions) = parse_input(input);
-
-    let mut pairs_count: BTreeMap<String, usize> = BTreeMap::new();
-    let mut element_count: BTreeMap<char, usize> = BTreeMap::new();
+pub struct PolymerEngine {
+    elements: Vec<char>,
+    template: Vec<usize>,
+    expansions: Vec<Vec<usize>>,
+}
 
-    for c in polymer.chars() {
-        *element_count.entry(c).or_insert(0) += 1
-    }
+impl PolymerEngine {
+    pub fn parse(input: &str) -> PolymerEngine {
+        let (polymer, insertions) = parse_input(input);
+
+        let mut elements: Vec<char> = polymer
+            .chars()
+            .chain(
+                insertions
+                    .iter()
+                    .flat_map(|(pair, insert)| pair.chars().chain(insert.chars())),
+            )
+            .collect();
+        elements.sort_unstable();
+        elements.dedup();
+
+        let n = elements.len();
+        let id = |c: char| elements.binary_search(&c).unwrap();
+        let template: Vec<usize> = polymer.chars().map(id).collect();
+
+        let mut expansions: Vec<Vec<usize>> = (0..n * n).map(|pair| vec![pair]).collect();
+        for (pair, insert) in &insertions {
+            let chain: Vec<usize> = pair
+                .chars()
+                .take(1)
+                .chain(insert.chars())
+                .chain(pair.chars().skip(1))
+                .map(id)
+                .collect();
+            let pair_idx = chain[0] * n + chain[chain.len() - 1];
+            expansions[pair_idx] = chain.windows(2).map(|w| w[0] * n + w[1]).collect();
+        }
 
-    for pair in char_windows(&polymer, 2) {
-        *pairs_count.entry(pair.to_string()).or_insert(0) += 1;
+        PolymerEngine {
+            elements,
+            template,
+            expansions,
+        }
     }
 
-    for _ in 0..steps {
-        let mut to_add: Vec<(String, usize)> = vec![];
-        let mut to_remove: Vec<(String, usize)> = vec![];
+    fn pair_counts<C>(&self, steps: usize) -> Vec<C>
+    where
+        C: Clone + Zero + From<u8> + for<'a> AddAssign<&'a C>,
+    {
+        let n = self.elements.len();
+        let mut counts: Vec<C> = vec![C::zero(); n * n];
+        for pair in self.template.windows(2) {
+            counts[pair[0] * n + pair[1]] += &C::from(1);
+        }
 
-        for (pair, insert) in &insertions {
-            let pair_str = pair.to_string();
-            let curr_pair_count = *pairs_count.get(&pair_str).unwrap_or(&0);
+        for _ in 0..steps {
+            let mut next: Vec<C> = vec![C::zero(); n * n];
+            for (pair, count) in counts.iter().enumerate() {
+                if count.is_zero() {
+                    continue;
+                }
+                for new_pair in &self.expansions[pair] {
+                    next[*new_pair] += count;
+                }
+            }
+            counts = next;
+        }
 
-            if curr_pair_count > 0 {
-                let left_insert = format!("{}{}", pair_str.chars().next().unwrap(), insert);
-                let right_insert = format!("{}{}", insert, pair_str.chars().nth(1).unwrap());
+        counts
+    }
 
-                let inserted_char = insertions.get(&pair_str).unwrap().chars().next().unwrap();
-                *element_count.entry(inserted_char).or_insert(0) += curr_pair_count;
+    pub fn histogram<C>(&self, steps: usize) -> BTreeMap<char, C>
+    where
+        C: Clone + Zero + From<u8> + for<'a> AddAssign<&'a C>,
+    {
+        let n = self.elements.len();
+        let mut totals: Vec<C> = vec![C::zero(); n];
 
-                to_add.push((left_insert, curr_pair_count));
-                to_add.push((right_insert, curr_pair_count));
-                to_remove.push((pair_str, curr_pair_count));
-            }
+        // Every element except the last one starts exactly one pair
+        for (pair, count) in self.pair_counts::<C>(steps).iter().enumerate() {
+            totals[pair / n] += count;
         }
-
-        for (key, value) in to_add {
-            // Add all new created pairs
-            *pairs_count.entry(key.to_string()).or_insert(0) += value;
+        if let Some(last) = self.template.last() {
+            totals[*last] += &C::from(1);
         }
 
-        for (key, value) in to_remove {
-            // Remove all old split pairs
-            *pairs_count.entry(key.to_string()).or_insert(0) -= value;
-        }
+        self.elements
+            .iter()
+            .zip(totals)
+            .filter(|(_, count)| !count.is_zero())
+            .map(|(element, count)| (*element, count))
+            .collect()
     }
-    let max = *element_count
-        .iter()
-        .max_by_key(|&(_, count)| count)
-        .unwrap()
-        .1;
-    let min = *element_count
-        .iter()
-        .min_by_key(|&(_, count)| count)
-        .unwrap()
-        .1;
+}
+
+pub fn min_max_diff(input: &str, steps: usize) -> usize {
+    let histogram = PolymerEngine::parse(input).histogram::<usize>(steps);
+
+    let max = histogram.values().max().unwrap();
+    let min = histogram.values().min().unwrap();
     max - min
 }
 
 fn parse_input(input: &str) -> (String, BTreeMap<String, String>) {
     let mut parts = input.split("\n\n").filter(|l| !l.is_empty());
 
-    let polymer: String = parts.next().unwrap().to_string();
+    let polymer: String = parts.next().unwrap().trim().to_string();
 
     let insertions: BTreeMap<_, _> = parts
         .next()
@@ -84,6 +127,9 @@ fn parse_input(input: &str) -> (String, BTreeMap<String, String>) {
         .filter(|l| !l.is_empty())
         .map(|l| {
             let insert_tokens: Vec<_> = l.trim().split(" -> ").filter(|l| !l.is_empty()).collect();
+            if insert_tokens.len() != 2 || insert_tokens[0].chars().count() != 2 {
+                panic!("Invalid insertion rule {}", l);
+            }
             (insert_tokens[0].to_string(), insert_tokens[1].to_string())
         })
         .collect();
@@ -95,9 +141,8 @@ fn parse_input(input: &str) -> (String, BTreeMap<String, String>) {
 mod tests {
     use super::*;
 
-    #[test]
-    fn test_min_max_diff() {
-        let input = "NNCB
+    fn example() -> &'static str {
+        "NNCB
 
 CH -> B
 HH -> N
@@ -114,8 +159,54 @@ BN -> B
 BB -> N
 BC -> B
 CC -> N
-CN -> C";
+CN -> C"
+    }
+
+    #[test]
+    fn test_min_max_diff() {
+        assert_eq!(min_max_diff(example(), 10), 1588);
+    }
+
+    #[test]
+    fn test_histogram() {
+        let engine = PolymerEngine::parse(example());
+        let histogram = engine.histogram::<usize>(10);
+
+        let expected: BTreeMap<char, usize> = vec![('B', 1749), ('C', 298), ('H', 161), ('N', 865)]
+            .into_iter()
+            .collect();
+        assert_eq!(histogram, expected);
+    }
+
+    #[test]
+    fn test_histogram_step_zero() {
+        let engine = PolymerEngine::parse(example());
+        let histogram = engine.histogram::<usize>(0);
+
+        let expected: BTreeMap<char, usize> =
+            vec![('B', 1), ('C', 1), ('N', 2)].into_iter().collect();
+        assert_eq!(histogram, expected);
+    }
+
+    #[test]
+    fn test_big_counts() {
+        let engine = PolymerEngine::parse(example());
+        let histogram = engine.histogram::<BigUint>(40);
+
+        assert_eq!(histogram[&'B'], BigUint::from(2192039569602u64));
+        assert_eq!(histogram[&'H'], BigUint::from(3849876073u64));
+    }
+
+    #[test]
+    fn test_multi_char_insertion() {
+        let input = "NN
+
+NN -> CN";
+        let engine = PolymerEngine::parse(input);
+
+        let histogram = engine.histogram::<usize>(3);
 
-        assert_eq!(min_max_diff(&input, 10), 1588);
+        let expected: BTreeMap<char, usize> = vec![('C', 3), ('N', 5)].into_iter().collect();
+        assert_eq!(histogram, expected);
     }
 }
@@ -1,5 +1,7 @@
 use std::cmp::Ordering;
-use std::collections::{BinaryHeap, HashMap};
+use std::collections::BinaryHeap;
+
+const MAX_RISK: i32 = 9;
 
 #[derive(Debug)]
 pub struct RiskMatrix {
@@ -32,104 +34,241 @@ impl Ord for RiskPoint {
     }
 }
 
-impl RiskMatrix {
-    fn get_index(&self, row: i32, column: i32) -> usize {
-        (row * self.width + column) as usize
+pub trait RiskGrid {
+    fn width(&self) -> i32;
+    fn height(&self) -> i32;
+    fn risk(&self, point: Point) -> i32;
+
+    fn tl_br_risk(&self) -> i32 {
+        let top_left = Point { row: 0, column: 0 };
+        let bottom_right = Point {
+            row: self.height() - 1,
+            column: self.width() - 1,
+        };
+        self.lowest_risk_path(top_left, bottom_right, Moves::Orthogonal)
+            .risk
     }
 
-    fn get_risk(&self, row: i32, column: i32) -> i32 {
-        self.risk_values[self.get_index(row, column)]
+    fn lowest_risk_path(&self, start: Point, end: Point, moves: Moves) -> RiskPath {
+        self.check_bounds(start);
+        self.check_bounds(end);
+
+        let mut search = Search::new(self, start);
+        let mut priority_queue: BinaryHeap<RiskPoint> = BinaryHeap::new();
+        priority_queue.push(RiskPoint {
+            coords: start,
+            risk: 0,
+        });
+
+        while let Some(current) = priority_queue.pop() {
+            if current.coords == end {
+                break;
+            }
+            if current.risk > search.risk_at(current.coords) {
+                continue;
+            }
+            for (next, risk) in search.relax(current.coords, moves) {
+                priority_queue.push(RiskPoint { coords: next, risk });
+            }
+        }
+
+        search.path_to(end)
     }
 
-    fn get_adjacent(&self, point: &RiskPoint) -> Vec<RiskPoint> {
-        let row = point.coords.row;
-        let col = point.coords.column;
-        let coords = vec![(0, 1), (0, -1), (-1, 0), (1, 0)];
-
-        coords
-            .into_iter()
-            .filter(|(dr, dc)| {
-                let r = row + dr;
-                let c = col + dc;
-                let oob = self.out_of_bounds(r, c);
-                !oob
-            })
-            .map(|(dr, dc)| {
-                let new_row = row + dr;
-                let new_col = col + dc;
-                let risk = self.get_risk(new_row, new_col);
-                RiskPoint {
-                    coords: Point {
-                        row: new_row,
-                        column: new_col,
-                    },
-                    risk,
+    // Dial's algorithm, a bucket queue works since every step costs at most MAX_RISK
+    fn lowest_risk_path_dial(&self, start: Point, end: Point, moves: Moves) -> RiskPath {
+        self.check_bounds(start);
+        self.check_bounds(end);
+
+        let mut search = Search::new(self, start);
+        let bucket_count = MAX_RISK as usize + 1;
+        let mut buckets: Vec<Vec<Point>> = vec![vec![]; bucket_count];
+        buckets[0].push(start);
+        let mut pending = 1;
+        let mut risk = 0;
+
+        'search: while pending > 0 {
+            let bucket = risk as usize % bucket_count;
+            while let Some(current) = buckets[bucket].pop() {
+                pending -= 1;
+                if search.risk_at(current) != risk {
+                    continue;
+                }
+                if current == end {
+                    break 'search;
                 }
-            })
-            .collect()
+                for (next, next_risk) in search.relax(current, moves) {
+                    buckets[next_risk as usize % bucket_count].push(next);
+                    pending += 1;
+                }
+            }
+            risk += 1;
+        }
+
+        search.path_to(end)
+    }
+
+    fn contains(&self, point: Point) -> bool {
+        point.row >= 0
+            && point.row < self.height()
+            && point.column >= 0
+            && point.column < self.width()
     }
 
-    fn out_of_bounds(&self, row: i32, column: i32) -> bool {
-        if row < 0 || row >= self.height {
-            return true;
+    fn check_bounds(&self, point: Point) {
+        if !self.contains(point) {
+            panic!(
+                "Point {:?} is outside of the {}x{} grid",
+                point,
+                self.width(),
+                self.height()
+            );
         }
-        if column < 0 || column >= self.width {
-            return true;
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Moves {
+    Orthogonal,
+    Diagonal,
+}
+
+impl Moves {
+    fn offsets(&self) -> &'static [(i32, i32)] {
+        match self {
+            Moves::Orthogonal => &[(0, 1), (0, -1), (-1, 0), (1, 0)],
+            Moves::Diagonal => &[
+                (0, 1),
+                (0, -1),
+                (-1, 0),
+                (1, 0),
+                (-1, -1),
+                (-1, 1),
+                (1, -1),
+                (1, 1),
+            ],
         }
-        false
     }
+}
 
-    fn find_risk(&self, start: Point, end: Point) -> i32 {
-        let mut came_from: HashMap<Point, Point> = HashMap::new();
-        let mut risk_so_far: HashMap<Point, i32> = HashMap::new();
-        let mut priority_queue: BinaryHeap<RiskPoint> = BinaryHeap::new();
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RiskPath {
+    pub risk: i32,
+    pub points: Vec<Point>,
+}
 
-        let current_point = start;
+struct Search<'a, G: RiskGrid + ?Sized> {
+    grid: &'a G,
+    risk_so_far: Vec<i32>,
+    came_from: Vec<Option<Point>>,
+}
 
-        let mut current = RiskPoint {
-            risk: 0,
-            coords: current_point,
+impl<'a, G: RiskGrid + ?Sized> Search<'a, G> {
+    fn new(grid: &'a G, start: Point) -> Search<'a, G> {
+        let size = (grid.width() * grid.height()) as usize;
+        let mut search = Search {
+            grid,
+            risk_so_far: vec![i32::MAX; size],
+            came_from: vec![None; size],
         };
+        let index = search.index(start);
+        search.risk_so_far[index] = 0;
+        search
+    }
 
-        priority_queue.push(current);
-        *came_from.entry(current_point).or_insert(current_point) = current_point;
-        *risk_so_far.entry(current_point).or_insert(0) = 0;
+    fn index(&self, point: Point) -> usize {
+        (point.row * self.grid.width() + point.column) as usize
+    }
 
-        while !priority_queue.is_empty() {
-            current = priority_queue.pop().unwrap();
-            if current.coords == end {
-                break;
-            }
+    fn risk_at(&self, point: Point) -> i32 {
+        self.risk_so_far[self.index(point)]
+    }
 
-            for adjacent in self.get_adjacent(&current) {
-                // set new risk of adjacent to the cost of path so far + adjacent risk
-                let new_risk = risk_so_far.get(&current.coords).unwrap() + adjacent.risk;
-
-                if !risk_so_far.contains_key(&adjacent.coords)
-                    || new_risk < *risk_so_far.get(&adjacent.coords).unwrap()
-                {
-                    *risk_so_far.entry(adjacent.coords).or_insert(new_risk) = new_risk;
-
-                    let priority = new_risk;
-                    priority_queue.push(RiskPoint {
-                        coords: adjacent.coords,
-                        risk: priority,
-                    });
-                    *came_from.entry(adjacent.coords).or_insert(current.coords) = current.coords;
-                }
+    // returns the neighbours whose risk improved, with their new risk
+    fn relax(&mut self, current: Point, moves: Moves) -> Vec<(Point, i32)> {
+        let current_risk = self.risk_at(current);
+        let mut improved = vec![];
+
+        for (dr, dc) in moves.offsets() {
+            let next = Point {
+                row: current.row + dr,
+                column: current.column + dc,
+            };
+            if !self.grid.contains(next) {
+                continue;
+            }
+            let new_risk = current_risk + self.grid.risk(next);
+            let index = self.index(next);
+            if new_risk < self.risk_so_far[index] {
+                self.risk_so_far[index] = new_risk;
+                self.came_from[index] = Some(current);
+                improved.push((next, new_risk));
             }
         }
 
-        *risk_so_far.get(&end).unwrap()
+        improved
     }
 
-    fn tl_br_risk(&self) -> i32 {
-        let top_left: Point = Point { row: 0, column: 0 };
-        let bottom_right: Point = Point {
-            row: self.height - 1,
-            column: self.width - 1,
-        };
-        self.find_risk(top_left, bottom_right)
+    fn path_to(&self, end: Point) -> RiskPath {
+        let mut points = vec![end];
+        let mut current = end;
+        while let Some(previous) = self.came_from[self.index(current)] {
+            points.push(previous);
+            current = previous;
+        }
+        points.reverse();
+
+        RiskPath {
+            risk: self.risk_at(end),
+            points,
+        }
+    }
+}
+
+impl RiskGrid for RiskMatrix {
+    fn width(&self) -> i32 {
+        self.width
+    }
+
+    fn height(&self) -> i32 {
+        self.height
+    }
+
+    fn risk(&self, point: Point) -> i32 {
+        self.risk_values[(point.row * self.width + point.column) as usize]
+    }
+}
+
+impl RiskMatrix {
+    pub fn tiled(&self, factor: i32) -> TiledView<'_> {
+        TiledView {
+            matrix: self,
+            factor,
+        }
+    }
+}
+
+pub struct TiledView<'a> {
+    matrix: &'a RiskMatrix,
+    factor: i32,
+}
+
+impl<'a> RiskGrid for TiledView<'a> {
+    fn width(&self) -> i32 {
+        self.matrix.width * self.factor
+    }
+
+    fn height(&self) -> i32 {
+        self.matrix.height * self.factor
+    }
+
+    fn risk(&self, point: Point) -> i32 {
+        let tile_shift = point.row / self.matrix.height + point.column / self.matrix.width;
+        let base = self.matrix.risk(Point {
+            row: point.row % self.matrix.height,
+            column: point.column % self.matrix.width,
+        });
+        (base - 1 + tile_shift) % MAX_RISK + 1
     }
 }
 
@@ -165,57 +304,14 @@ fn parse_values(input: &[String]) -> Vec<Vec<i32>> {
         .collect()
 }
 
-fn parse_extended(input: &[String]) -> RiskMatrix {
-    let values = parse_and_extend(input);
-    let height = values.len() as i32;
-    let width = values[0].len() as i32;
-    let flat = values.iter().flatten().cloned().collect();
-    RiskMatrix {
-        risk_values: flat,
-        height,
-        width,
-    }
-}
-
-fn parse_and_extend(input: &[String]) -> Vec<Vec<i32>> {
-    let initial = parse_values(input);
-
-    let mut lines: Vec<Vec<i32>> = vec![];
-
-    for idx in 0..5 {
-        let shifted: Vec<Vec<i32>> = initial
-            .iter()
-            .map(|n| n.iter().map(|n| conv(*n, idx)).collect())
-            .collect();
-
-        for row in shifted.iter() {
-            let mut result: Vec<i32> = vec![];
-            for i in 0..5 {
-                result.extend(row.iter().map(|n| conv(*n, i)))
-            }
-            lines.push(result)
-        }
-    }
-
-    lines
-}
-
-fn conv(n: i32, idx: i32) -> i32 {
-    let shifted = n + idx;
-    if shifted == 9 {
-        return shifted;
-    }
-    (shifted) % 9
-}
-
 fn part_1(input: &[String]) -> i32 {
     let matrix = parse_input(input);
     matrix.tl_br_risk()
 }
 
 fn part_2(input: &[String]) -> i32 {
-    let matrix = parse_extended(input);
-    matrix.tl_br_risk()
+    let matrix = parse_input(input);
+    matrix.tiled(5).tl_br_risk()
 }
 
 pub fn run(input: &[String]) {
@@ -224,6 +320,20 @@ pub fn run(input: &[String]) {
 
     let extended_risk = part_2(input);
     println!("Lowest extended risk: {}", extended_risk);
+
+    let matrix = parse_input(input);
+    let tiled = matrix.tiled(5);
+    let start = Point { row: 0, column: 0 };
+    let end = Point {
+        row: tiled.height() - 1,
+        column: tiled.width() - 1,
+    };
+    let path = tiled.lowest_risk_path_dial(start, end, Moves::Diagonal);
+    println!(
+        "Lowest extended risk with diagonal moves: {} over {} points",
+        path.risk,
+        path.points.len()
+    );
 }
 
 #[cfg(test)]
@@ -258,8 +368,83 @@ mod tests {
     #[test]
     fn test_risk_extended() {
         let input = mock_input();
-        let mat = parse_extended(&input);
-        let risk = mat.tl_br_risk();
+        let mat = parse_input(&input);
+        let risk = mat.tiled(5).tl_br_risk();
         assert_eq!(risk, 315);
     }
+
+    #[test]
+    fn test_tiled_view() {
+        let input = vec_of_strings!["8"];
+        let mat = parse_input(&input);
+        let tiled = mat.tiled(5);
+        let row: Vec<i32> = (0..5)
+            .map(|column| tiled.risk(Point { row: 0, column }))
+            .collect();
+        assert_eq!(row, vec![8, 9, 1, 2, 3]);
+        assert_eq!(tiled.risk(Point { row: 4, column: 4 }), 7);
+        assert_eq!((tiled.width(), tiled.height()), (5, 5));
+    }
+
+    #[test]
+    fn test_path_between_points() {
+        let input = vec_of_strings!["191", "111"];
+        let mat = parse_input(&input);
+        let start = Point { row: 0, column: 2 };
+        let end = Point { row: 0, column: 0 };
+
+        let path = mat.lowest_risk_path(start, end, Moves::Orthogonal);
+
+        let expected = vec![
+            Point { row: 0, column: 2 },
+            Point { row: 1, column: 2 },
+            Point { row: 1, column: 1 },
+            Point { row: 1, column: 0 },
+            Point { row: 0, column: 0 },
+        ];
+        assert_eq!(path.risk, 4);
+        assert_eq!(path.points, expected);
+    }
+
+    #[test]
+    fn test_diagonal_moves() {
+        let input = vec_of_strings!["199", "919", "991"];
+        let mat = parse_input(&input);
+        let start = Point { row: 0, column: 0 };
+        let end = Point { row: 2, column: 2 };
+
+        let orthogonal = mat.lowest_risk_path(start, end, Moves::Orthogonal);
+        let diagonal = mat.lowest_risk_path(start, end, Moves::Diagonal);
+
+        assert_eq!(orthogonal.risk, 20);
+        assert_eq!(diagonal.risk, 2);
+        assert_eq!(diagonal.points.len(), 3);
+    }
+
+    #[test]
+    fn test_dial_matches_dijkstra() {
+        let input = mock_input();
+        let mat = parse_input(&input);
+        let tiled = mat.tiled(5);
+        let start = Point { row: 3, column: 7 };
+        let end = Point {
+            row: 41,
+            column: 12,
+        };
+
+        for moves in [Moves::Orthogonal, Moves::Diagonal] {
+            let dijkstra = tiled.lowest_risk_path(start, end, moves);
+            let dial = tiled.lowest_risk_path_dial(start, end, moves);
+            assert_eq!(dial.risk, dijkstra.risk);
+            let path_risk: i32 = dial.points[1..].iter().map(|p| tiled.risk(*p)).sum();
+            assert_eq!(path_risk, dial.risk);
+        }
+        let corner = Point {
+            row: 49,
+            column: 49,
+        };
+        let origin = Point { row: 0, column: 0 };
+        let dial = tiled.lowest_risk_path_dial(origin, corner, Moves::Orthogonal);
+        assert_eq!(dial.risk, 315);
+    }
 }
@@ -1,200 +1,505 @@
+use std::fmt;
+use std::fmt::Formatter;
+
 pub fn run(input: &str) {
-    let sum = version_sum(input);
-    println!("Total sum: {}", sum);
-    let eval = eval_packets(input);
-    println!("After eval: {}", eval);
-}
+    let packet = match decode(input) {
+        Ok(packet) => packet,
+        Err(e) => panic!("Invalid transmission: {}", e),
+    };
 
-#[derive(Eq, PartialEq, Debug)]
-pub enum PacketType {
-    LiteralValue = 0,
-    Operator = 1,
+    println!("Total sum: {}", packet.version_sum());
+    match packet.eval() {
+        Ok(value) => println!("After eval: {}", value),
+        Err(e) => println!("Cannot evaluate transmission: {}", e),
+    }
+
+    println!("Expression: {}", packet);
+    let roundtrip = encode(&packet).and_then(|hex| decode(&hex));
+    println!(
+        "Re-encoded packet decodes the same: {}",
+        roundtrip == Ok(packet)
+    );
 }
 
-#[derive(Eq, PartialEq, Debug)]
+const LITERAL_TYPE_ID: u8 = 4;
+
+#[derive(Eq, PartialEq, Debug, Copy, Clone)]
 pub enum LengthType {
     SubPacketsTotalLength = 0,
     NumSubPackets = 1,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Eq, PartialEq, Debug, Copy, Clone)]
+pub enum Operator {
+    Sum = 0,
+    Product = 1,
+    Minimum = 2,
+    Maximum = 3,
+    GreaterThan = 5,
+    LessThan = 6,
+    EqualTo = 7,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Payload {
+    Literal(u128),
+    Operator {
+        operator: Operator,
+        length_type: LengthType,
+        subpackets: Vec<Packet>,
+    },
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
 pub struct Packet {
     version: u8,
-    type_id: u8,
-    subpackets: Vec<Packet>,
-    value: usize,
+    payload: Payload,
+}
+
+#[derive(Debug, Eq, PartialEq)]
+pub enum PacketErrorKind {
+    InvalidHex(usize, char),
+    UnexpectedEnd,
+    LiteralOverflow,
+    LengthMismatch { expected: usize, actual: usize },
+    Arity { operator: Operator, found: usize },
+    Overflow(Operator),
+    FieldOverflow { value: usize, bits: usize },
 }
 
-fn parse_packet(mut offset: usize, binary: &str) -> (Packet, usize) {
-    let version = version(offset, binary);
-    offset += 3;
-    let type_id = type_id(offset, binary);
-    offset += 3;
+#[derive(Debug, Eq, PartialEq)]
+pub struct PacketError {
+    path: Vec<usize>,
+    kind: PacketErrorKind,
+}
 
-    match packet_type(type_id) {
-        PacketType::LiteralValue => parse_literal(offset, version, type_id, binary),
-        PacketType::Operator => parse_operator(offset, version, type_id, binary),
-    }
+pub struct BitReader {
+    bytes: Vec<u8>,
+    len: usize,
+    offset: usize,
 }
 
-fn parse_literal(mut offset: usize, version: u8, type_id: u8, binary: &str) -> (Packet, usize) {
-    let mut literal_str = "".to_string();
+#[derive(Default)]
+pub struct BitWriter {
+    bytes: Vec<u8>,
+    len: usize,
+}
 
-    while binary.chars().nth(offset).unwrap() == '1' {
-        let start = offset + 1;
-        let end = offset + 5;
-        literal_str += &binary[start..end];
-        offset += 5;
+impl Operator {
+    fn from_type_id(type_id: u8) -> Option<Operator> {
+        match type_id {
+            0 => Some(Operator::Sum),
+            1 => Some(Operator::Product),
+            2 => Some(Operator::Minimum),
+            3 => Some(Operator::Maximum),
+            5 => Some(Operator::GreaterThan),
+            6 => Some(Operator::LessThan),
+            7 => Some(Operator::EqualTo),
+            _ => None,
+        }
     }
 
-    let start = offset + 1;
-    let end = offset + 5;
-    literal_str += &binary[start..end];
+    fn name(&self) -> &'static str {
+        match self {
+            Operator::Sum => "sum",
+            Operator::Product => "product",
+            Operator::Minimum => "min",
+            Operator::Maximum => "max",
+            Operator::GreaterThan => "gt",
+            Operator::LessThan => "lt",
+            Operator::EqualTo => "eq",
+        }
+    }
 
-    offset += 5;
-    let value = usize::from_str_radix(&literal_str, 2).unwrap();
-    let packet = Packet {
-        version,
-        type_id,
-        value,
-        subpackets: vec![],
-    };
-    (packet, offset)
-}
+    fn accepts(&self, operands: usize) -> bool {
+        match self {
+            Operator::Sum | Operator::Product | Operator::Minimum | Operator::Maximum => {
+                operands >= 1
+            }
+            Operator::GreaterThan | Operator::LessThan | Operator::EqualTo => operands == 2,
+        }
+    }
 
-fn parse_operator(mut offset: usize, version: u8, type_id: u8, binary: &str) -> (Packet, usize) {
-    let mut subpackets: Vec<Packet> = vec![];
-    let len_type = length_type(offset, binary);
-    offset += 1;
+    fn apply(&self, operands: &[u128]) -> Option<u128> {
+        let mut values = operands.iter().copied();
+        match self {
+            Operator::Sum => values.try_fold(0u128, |acc, v| acc.checked_add(v)),
+            Operator::Product => values.try_fold(1u128, |acc, v| acc.checked_mul(v)),
+            Operator::Minimum => values.min(),
+            Operator::Maximum => values.max(),
+            Operator::GreaterThan => Some((operands[0] > operands[1]).into()),
+            Operator::LessThan => Some((operands[0] < operands[1]).into()),
+            Operator::EqualTo => Some((operands[0] == operands[1]).into()),
+        }
+    }
+}
 
-    match len_type {
-        LengthType::SubPacketsTotalLength => {
-            let len = subpackets_length(offset, binary);
-            offset += 15;
-            let target_offset = offset + len;
-            while offset < target_offset {
-                let (child_packet, new_offset) = parse_packet(offset, binary);
-                subpackets.push(child_packet);
-                offset = new_offset;
+impl Packet {
+    pub fn version_sum(&self) -> usize {
+        let children = match &self.payload {
+            Payload::Literal(_) => 0,
+            Payload::Operator { subpackets, .. } => {
+                subpackets.iter().map(|p| p.version_sum()).sum()
             }
+        };
+        self.version as usize + children
+    }
+
+    pub fn eval(&self) -> Result<u128, PacketError> {
+        self.eval_at(&mut vec![])
+    }
+
+    fn eval_at(&self, path: &mut Vec<usize>) -> Result<u128, PacketError> {
+        let (operator, subpackets) = match &self.payload {
+            Payload::Literal(value) => return Ok(*value),
+            Payload::Operator {
+                operator,
+                subpackets,
+                ..
+            } => (operator, subpackets),
+        };
+
+        let mut operands: Vec<u128> = Vec::with_capacity(subpackets.len());
+        for (idx, child) in subpackets.iter().enumerate() {
+            path.push(idx);
+            operands.push(child.eval_at(path)?);
+            path.pop();
         }
-        LengthType::NumSubPackets => {
-            let num_subpackets = subpackets_num(offset, binary);
-            offset += 11;
-            for _ in 0..num_subpackets {
-                let (child_packet, new_offset) = parse_packet(offset, binary);
-                subpackets.push(child_packet);
-                offset = new_offset
-            }
+
+        if !operator.accepts(operands.len()) {
+            return Err(PacketError::at(
+                path,
+                PacketErrorKind::Arity {
+                    operator: *operator,
+                    found: operands.len(),
+                },
+            ));
         }
+
+        operator
+            .apply(&operands)
+            .ok_or_else(|| PacketError::at(path, PacketErrorKind::Overflow(*operator)))
     }
+}
 
-    (
-        Packet {
-            version,
-            type_id,
-            subpackets,
-            value: 0,
-        },
-        offset,
-    )
+impl PacketError {
+    fn at(path: &[usize], kind: PacketErrorKind) -> PacketError {
+        PacketError {
+            path: path.to_vec(),
+            kind,
+        }
+    }
 }
 
-fn eval_packet(p: Packet) -> usize {
-    let children: Vec<usize> = p.subpackets.into_iter().map(eval_packet).collect();
-    match p.type_id {
-        0 => children.into_iter().sum(),
-        1 => children.into_iter().product(),
-        2 => children.into_iter().min().unwrap(),
-        3 => children.into_iter().max().unwrap(),
-        4 => p.value,
-        5 => (children[0] > children[1]).into(),
-        6 => (children[0] < children[1]).into(),
-        7 => (children[0] == children[1]).into(),
-        _ => unreachable!(),
+impl fmt::Display for PacketError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "packet /")?;
+        let path: Vec<String> = self.path.iter().map(|idx| idx.to_string()).collect();
+        write!(f, "{}: ", path.join("/"))?;
+
+        match &self.kind {
+            PacketErrorKind::InvalidHex(pos, c) => {
+                write!(f, "invalid hex digit '{}' at position {}", c, pos)
+            }
+            PacketErrorKind::UnexpectedEnd => write!(f, "unexpected end of transmission"),
+            PacketErrorKind::LiteralOverflow => write!(f, "literal does not fit in 128 bits"),
+            PacketErrorKind::LengthMismatch { expected, actual } => write!(
+                f,
+                "subpackets take {} bits, but {} were declared",
+                actual, expected
+            ),
+            PacketErrorKind::Arity { operator, found } => {
+                write!(f, "{} cannot take {} operands", operator.name(), found)
+            }
+            PacketErrorKind::Overflow(operator) => {
+                write!(f, "{} overflows 128 bits", operator.name())
+            }
+            PacketErrorKind::FieldOverflow { value, bits } => {
+                write!(f, "{} does not fit in a {}-bit field", value, bits)
+            }
+        }
     }
 }
 
-fn sum_version(p: Packet) -> usize {
-    p.version as usize + p.subpackets.into_iter().map(sum_version).sum::<usize>()
+impl BitReader {
+    pub fn from_hex(hex: &str) -> Result<BitReader, PacketErrorKind> {
+        let bytes = hex_to_bytes(hex)?;
+        Ok(BitReader {
+            len: hex.trim().len() * 4,
+            bytes,
+            offset: 0,
+        })
+    }
+
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    pub fn read_bit(&mut self) -> Result<bool, PacketErrorKind> {
+        if self.offset >= self.len {
+            return Err(PacketErrorKind::UnexpectedEnd);
+        }
+
+        let byte = self.bytes[self.offset / 8];
+        let bit = (byte >> (7 - self.offset % 8)) & 1;
+        self.offset += 1;
+        Ok(bit == 1)
+    }
+
+    pub fn read(&mut self, bits: usize) -> Result<usize, PacketErrorKind> {
+        let mut value = 0;
+        for _ in 0..bits {
+            value = (value << 1) | self.read_bit()? as usize;
+        }
+        Ok(value)
+    }
 }
 
-fn version_sum(input: &str) -> usize {
-    let binary = hex_to_bin(input);
-    let (p, _) = parse_packet(0, &binary);
-    sum_version(p)
+impl BitWriter {
+    pub fn write_bit(&mut self, bit: bool) {
+        if self.len == self.bytes.len() * 8 {
+            self.bytes.push(0);
+        }
+        if bit {
+            let last = self.bytes.len() - 1;
+            self.bytes[last] |= 1 << (7 - self.len % 8);
+        }
+        self.len += 1;
+    }
+
+    pub fn write(&mut self, value: usize, bits: usize) -> Result<(), PacketErrorKind> {
+        if bits < usize::BITS as usize && value >> bits != 0 {
+            return Err(PacketErrorKind::FieldOverflow { value, bits });
+        }
+        for shift in (0..bits).rev() {
+            self.write_bit((value >> shift) & 1 == 1);
+        }
+        Ok(())
+    }
+
+    pub fn append(&mut self, other: &BitWriter) {
+        for idx in 0..other.len {
+            self.write_bit((other.bytes[idx / 8] >> (7 - idx % 8)) & 1 == 1);
+        }
+    }
+
+    pub fn to_hex(&self) -> String {
+        let mut hex: String = self.bytes.iter().map(|b| format!("{:02X}", b)).collect();
+        if (1..=4).contains(&(self.len % 8)) {
+            hex.pop();
+        }
+        hex
+    }
 }
 
-fn eval_packets(input: &str) -> usize {
-    let binary = hex_to_bin(input);
-    let (p, _) = parse_packet(0, &binary);
-    eval_packet(p)
+fn parse_packet(reader: &mut BitReader, path: &mut Vec<usize>) -> Result<Packet, PacketError> {
+    let header = reader
+        .read(3)
+        .and_then(|version| Ok((version as u8, reader.read(3)? as u8)));
+    let (version, type_id) = header.map_err(|kind| PacketError::at(path, kind))?;
+
+    let payload = match Operator::from_type_id(type_id) {
+        None => parse_literal(reader).map_err(|kind| PacketError::at(path, kind))?,
+        Some(operator) => parse_operator(reader, operator, path)?,
+    };
+
+    Ok(Packet { version, payload })
 }
 
-pub fn version(offset: usize, binary: &str) -> u8 {
-    let version_str = &binary[offset..offset + 3];
-    u8::from_str_radix(version_str, 2).unwrap()
+fn parse_literal(reader: &mut BitReader) -> Result<Payload, PacketErrorKind> {
+    let mut value: u128 = 0;
+
+    loop {
+        let has_more = reader.read_bit()?;
+        if value >> (u128::BITS - 4) != 0 {
+            return Err(PacketErrorKind::LiteralOverflow);
+        }
+        value = (value << 4) | reader.read(4)? as u128;
+        if !has_more {
+            break;
+        }
+    }
+
+    Ok(Payload::Literal(value))
 }
 
-pub fn subpackets_length(offset: usize, binary: &str) -> usize {
-    let length_str = &binary[offset..offset + 15];
-    usize::from_str_radix(length_str, 2).unwrap()
+fn parse_operator(
+    reader: &mut BitReader,
+    operator: Operator,
+    path: &mut Vec<usize>,
+) -> Result<Payload, PacketError> {
+    let mut subpackets: Vec<Packet> = vec![];
+    let at = |path: &[usize], kind| PacketError::at(path, kind);
+    let length_type = length_type(reader.read_bit().map_err(|kind| at(path, kind))?);
+
+    match length_type {
+        LengthType::SubPacketsTotalLength => {
+            let len = reader.read(15).map_err(|kind| at(path, kind))?;
+            let start = reader.offset();
+            while reader.offset() < start + len {
+                path.push(subpackets.len());
+                subpackets.push(parse_packet(reader, path)?);
+                path.pop();
+            }
+            if reader.offset() != start + len {
+                let kind = PacketErrorKind::LengthMismatch {
+                    expected: len,
+                    actual: reader.offset() - start,
+                };
+                return Err(at(path, kind));
+            }
+        }
+        LengthType::NumSubPackets => {
+            let num_subpackets = reader.read(11).map_err(|kind| at(path, kind))?;
+            for idx in 0..num_subpackets {
+                path.push(idx);
+                subpackets.push(parse_packet(reader, path)?);
+                path.pop();
+            }
+        }
+    }
+
+    if !operator.accepts(subpackets.len()) {
+        let kind = PacketErrorKind::Arity {
+            operator,
+            found: subpackets.len(),
+        };
+        return Err(at(path, kind));
+    }
+
+    Ok(Payload::Operator {
+        operator,
+        length_type,
+        subpackets,
+    })
 }
 
-pub fn subpackets_num(offset: usize, binary: &str) -> usize {
-    let num_str = &binary[offset..offset + 11];
-    usize::from_str_radix(num_str, 2).unwrap()
+fn write_packet(
+    p: &Packet,
+    writer: &mut BitWriter,
+    path: &mut Vec<usize>,
+) -> Result<(), PacketError> {
+    let at = |kind| PacketError::at(path, kind);
+    writer.write(p.version as usize, 3).map_err(at)?;
+
+    match &p.payload {
+        Payload::Literal(value) => {
+            writer.write(LITERAL_TYPE_ID as usize, 3).map_err(at)?;
+            write_literal(*value, writer).map_err(at)?;
+        }
+        Payload::Operator {
+            operator,
+            length_type,
+            subpackets,
+        } => {
+            writer.write(*operator as usize, 3).map_err(at)?;
+
+            let mut children = BitWriter::default();
+            for (idx, child) in subpackets.iter().enumerate() {
+                path.push(idx);
+                write_packet(child, &mut children, path)?;
+                path.pop();
+            }
+
+            let at = |kind| PacketError::at(path, kind);
+            match length_type {
+                LengthType::SubPacketsTotalLength => {
+                    writer.write_bit(false);
+                    writer.write(children.len, 15).map_err(at)?;
+                }
+                LengthType::NumSubPackets => {
+                    writer.write_bit(true);
+                    writer.write(subpackets.len(), 11).map_err(at)?;
+                }
+            }
+            writer.append(&children);
+        }
+    }
+    Ok(())
 }
 
-fn hex_to_bin(hex: &str) -> String {
-    hex.chars().map(to_binary).collect()
+fn write_literal(value: u128, writer: &mut BitWriter) -> Result<(), PacketErrorKind> {
+    let mut groups = 1;
+    while groups * 4 < u128::BITS as usize && value >> (groups * 4) != 0 {
+        groups += 1;
+    }
+
+    for group in (0..groups).rev() {
+        writer.write_bit(group > 0);
+        writer.write(((value >> (group * 4)) & 0xF) as usize, 4)?;
+    }
+    Ok(())
 }
 
-fn to_binary(c: char) -> &'static str {
-    match c {
-        '0' => "0000",
-        '1' => "0001",
-        '2' => "0010",
-        '3' => "0011",
-        '4' => "0100",
-        '5' => "0101",
-        '6' => "0110",
-        '7' => "0111",
-        '8' => "1000",
-        '9' => "1001",
-        'A' => "1010",
-        'B' => "1011",
-        'C' => "1100",
-        'D' => "1101",
-        'E' => "1110",
-        'F' => "1111",
-        _ => "",
+impl fmt::Display for Packet {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match &self.payload {
+            Payload::Literal(value) => write!(f, "(lit {})", value),
+            Payload::Operator {
+                operator,
+                subpackets,
+                ..
+            } => {
+                write!(f, "({}", operator.name())?;
+                for child in subpackets {
+                    write!(f, " {}", child)?;
+                }
+                write!(f, ")")
+            }
+        }
     }
 }
 
-fn type_id(offset: usize, binary: &str) -> u8 {
-    let version_str = &binary[offset..offset + 3];
-    u8::from_str_radix(version_str, 2).unwrap()
+fn decode(input: &str) -> Result<Packet, PacketError> {
+    let mut reader = BitReader::from_hex(input).map_err(|kind| PacketError::at(&[], kind))?;
+    parse_packet(&mut reader, &mut vec![])
 }
 
-pub fn packet_type(type_id: u8) -> PacketType {
-    match type_id {
-        4 => PacketType::LiteralValue,
-        _ => PacketType::Operator,
-    }
+fn encode(p: &Packet) -> Result<String, PacketError> {
+    let mut writer = BitWriter::default();
+    write_packet(p, &mut writer, &mut vec![])?;
+    Ok(writer.to_hex())
 }
 
-fn length_type(offset: usize, binary: &str) -> LengthType {
-    match binary.chars().nth(offset).unwrap() {
-        '0' => LengthType::SubPacketsTotalLength,
-        '1' => LengthType::NumSubPackets,
-        _ => unreachable!(),
+fn hex_to_bytes(hex: &str) -> Result<Vec<u8>, PacketErrorKind> {
+    let nibbles = hex
+        .trim()
+        .chars()
+        .enumerate()
+        .map(|(pos, c)| {
+            c.to_digit(16)
+                .map(|d| d as u8)
+                .ok_or(PacketErrorKind::InvalidHex(pos, c))
+        })
+        .collect::<Result<Vec<u8>, _>>()?;
+
+    Ok(nibbles
+        .chunks(2)
+        .map(|pair| (pair[0] << 4) | pair.get(1).unwrap_or(&0))
+        .collect())
+}
+
+fn length_type(bit: bool) -> LengthType {
+    match bit {
+        false => LengthType::SubPacketsTotalLength,
+        true => LengthType::NumSubPackets,
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::aoc_2021::day16::version_sum;
+    use crate::aoc_2021::day16::{
+        decode, encode, BitReader, BitWriter, LengthType, Operator, Packet, PacketError,
+        PacketErrorKind, Payload,
+    };
+
+    fn version_sum(input: &str) -> usize {
+        decode(input).unwrap().version_sum()
+    }
+
+    fn eval_packets(input: &str) -> u128 {
+        decode(input).unwrap().eval().unwrap()
+    }
 
     #[test]
 
@@ -231,4 +536,210 @@ mod tests {
         let sum = version_sum(input);
         assert_eq!(sum, 31);
     }
+
+    #[test]
+    fn test_eval_packets() {
+        let cases = [
+            ("C200B40A82", 3),
+            ("04005AC33890", 54),
+            ("880086C3E88112", 7),
+            ("CE00C43D881120", 9),
+            ("D8005AC2A8F0", 1),
+            ("F600BC2D8F", 0),
+            ("9C005AC2F8F0", 0),
+            ("9C0141080250320F1802104A08", 1),
+        ];
+
+        for (input, expected) in cases.iter() {
+            assert_eq!(eval_packets(input), *expected, "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_bit_reader() {
+        let mut reader = BitReader::from_hex("D2FE28").unwrap();
+        assert_eq!(reader.read(3), Ok(6));
+        assert_eq!(reader.read(3), Ok(4));
+        assert_eq!(reader.read_bit(), Ok(true));
+        assert_eq!(reader.read(4), Ok(0b0111));
+        assert_eq!(reader.offset(), 11);
+        assert_eq!(reader.read(13), Ok(0b1_1110_0010_1000));
+        assert_eq!(reader.read(1), Err(PacketErrorKind::UnexpectedEnd));
+    }
+
+    #[test]
+    fn test_encode_roundtrip() {
+        let inputs = [
+            "D2FE28",
+            "38006F45291200",
+            "EE00D40C823060",
+            "8A004A801A8002F478",
+            "620080001611562C8802118E34",
+            "C0015000016115A2E0802F182340",
+            "A0016C880162017C3686B18A3D4780",
+            "9C0141080250320F1802104A08",
+        ];
+
+        for input in inputs.iter() {
+            let packet = decode(input).unwrap();
+            let encoded = encode(&packet).unwrap();
+            assert_eq!(decode(&encoded), Ok(packet), "{}", input);
+            assert!(input.starts_with(&encoded), "{} vs {}", input, encoded);
+            assert!(input[encoded.len()..].chars().all(|c| c == '0'));
+        }
+    }
+
+    #[test]
+    fn test_pretty_print() {
+        assert_eq!(decode("D2FE28").unwrap().to_string(), "(lit 2021)");
+        assert_eq!(
+            decode("9C0141080250320F1802104A08").unwrap().to_string(),
+            "(eq (sum (lit 1) (lit 3)) (product (lit 2) (lit 2)))"
+        );
+        assert_eq!(
+            decode("880086C3E88112").unwrap().to_string(),
+            "(min (lit 7) (lit 8) (lit 9))"
+        );
+    }
+
+    fn lit(value: u128) -> Packet {
+        Packet {
+            version: 1,
+            payload: Payload::Literal(value),
+        }
+    }
+
+    fn op(operator: Operator, subpackets: Vec<Packet>) -> Packet {
+        Packet {
+            version: 2,
+            payload: Payload::Operator {
+                operator,
+                length_type: LengthType::NumSubPackets,
+                subpackets,
+            },
+        }
+    }
+
+    #[test]
+    fn test_large_literal() {
+        let packet = lit((1u128 << 100) - 1);
+        let decoded = decode(&encode(&packet).unwrap()).unwrap();
+        assert_eq!(decoded.eval(), Ok((1u128 << 100) - 1));
+    }
+
+    #[test]
+    fn test_rejects_invalid_hex() {
+        assert_eq!(
+            decode("D2XE28"),
+            Err(PacketError {
+                path: vec![],
+                kind: PacketErrorKind::InvalidHex(2, 'X')
+            })
+        );
+    }
+
+    #[test]
+    fn test_rejects_truncated_packet() {
+        let err = decode("8A004A801A80").unwrap_err();
+        assert_eq!(err.path, vec![0, 0]);
+        assert_eq!(err.kind, PacketErrorKind::UnexpectedEnd);
+    }
+
+    #[test]
+    fn test_rejects_comparison_arity() {
+        // given
+        let comparison = op(Operator::GreaterThan, vec![lit(1), lit(2), lit(3)]);
+        let packet = op(Operator::Sum, vec![lit(4), comparison]);
+
+        // when
+        let err = decode(&encode(&packet).unwrap()).unwrap_err();
+
+        // then
+        let kind = PacketErrorKind::Arity {
+            operator: Operator::GreaterThan,
+            found: 3,
+        };
+        assert_eq!(err, PacketError::at(&[1], kind));
+        assert_eq!(err.to_string(), "packet /1: gt cannot take 3 operands");
+    }
+
+    #[test]
+    fn test_rejects_empty_minimum() {
+        let packet = op(Operator::Minimum, vec![]);
+        let err = decode(&encode(&packet).unwrap()).unwrap_err();
+        let kind = PacketErrorKind::Arity {
+            operator: Operator::Minimum,
+            found: 0,
+        };
+        assert_eq!(err, PacketError::at(&[], kind));
+    }
+
+    #[test]
+    fn test_reports_overflow_path() {
+        // given
+        let product = op(Operator::Product, vec![lit(1 << 127), lit(2)]);
+        let packet = op(Operator::Sum, vec![lit(1), product]);
+        let decoded = decode(&encode(&packet).unwrap()).unwrap();
+
+        // when
+        let result = decoded.eval();
+
+        // then
+        let err = PacketError::at(&[1], PacketErrorKind::Overflow(Operator::Product));
+        assert_eq!(result, Err(err));
+    }
+
+    #[test]
+    fn test_bit_writer_rejects_wide_values() {
+        let mut writer = BitWriter::default();
+        assert_eq!(writer.write(7, 3), Ok(()));
+        assert_eq!(
+            writer.write(8, 3),
+            Err(PacketErrorKind::FieldOverflow { value: 8, bits: 3 })
+        );
+        assert_eq!(writer.to_hex(), "E");
+    }
+
+    #[test]
+    fn test_encode_rejects_field_overflow() {
+        // given
+        let long = Packet {
+            version: 3,
+            payload: Payload::Operator {
+                operator: Operator::Sum,
+                length_type: LengthType::SubPacketsTotalLength,
+                subpackets: vec![lit(1); 3000],
+            },
+        };
+        let many = op(Operator::Sum, vec![lit(1); 2048]);
+        let mut versioned = lit(1);
+        versioned.version = 8;
+        let nested = op(Operator::Sum, vec![lit(1), versioned]);
+
+        // when
+        let results = [encode(&long), encode(&many), encode(&nested)];
+
+        // then
+        let expected = [
+            PacketError::at(
+                &[],
+                PacketErrorKind::FieldOverflow {
+                    value: 33000,
+                    bits: 15,
+                },
+            ),
+            PacketError::at(
+                &[],
+                PacketErrorKind::FieldOverflow {
+                    value: 2048,
+                    bits: 11,
+                },
+            ),
+            PacketError::at(&[1], PacketErrorKind::FieldOverflow { value: 8, bits: 3 }),
+        ];
+        for (result, expected) in results.iter().zip(expected.iter()) {
+            assert_eq!(result.as_ref(), Err(expected));
+        }
+        assert!(encode(&op(Operator::Sum, vec![lit(1); 2047])).is_ok());
+    }
 }
@@ -1,31 +1,41 @@
-use itertools::Itertools;
 use std::cmp::Ordering;
-
-pub struct Position {
-    x: i32,
-    y: i32,
+use std::fmt;
+use std::fmt::Formatter;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Hit {
+    pub x_vel: i32,
+    pub y_vel: i32,
+    pub apex: i32,
 }
 
-pub struct Probe {
-    pos: Position,
-    x_vel: i32,
-    y_vel: i32,
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TrajectoryError {
+    InfiniteVelocities,
 }
 
-impl Probe {
-    pub fn new(x_vel: i32, y_vel: i32) -> Probe {
-        Probe {
-            pos: Position { x: 0, y: 0 },
-            x_vel,
-            y_vel,
+impl fmt::Display for TrajectoryError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            TrajectoryError::InfiniteVelocities => write!(
+                f,
+                "probe can stall inside the target at launcher height, infinitely many velocities hit"
+            ),
         }
     }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct StepRange {
+    first: i32,
+    // None when the probe stays in range forever
+    last: Option<i32>,
+}
 
-    pub fn step(&mut self) {
-        self.pos.x += self.x_vel;
-        self.pos.y += self.y_vel;
-        self.x_vel = apply_drag(self.x_vel);
-        self.y_vel = apply_gravity(self.y_vel);
+impl StepRange {
+    fn overlaps(&self, other: &StepRange) -> bool {
+        let ends_after = |range: &StepRange, step: i32| !matches!(range.last, Some(l) if l < step);
+        ends_after(self, other.first) && ends_after(other, self.first)
     }
 }
 
@@ -41,59 +51,128 @@ fn apply_gravity(y_vel: i32) -> i32 {
     y_vel - 1
 }
 
-fn find_max_y(x_vel: i32, y_vel: i32, area: &Area) -> Option<i32> {
-    let mut probe = Probe::new(x_vel, y_vel);
-    let mut y_max = 0;
-
-    loop {
-        probe.step();
-        let curr_y = probe.pos.y;
+fn apex(y_vel: i32) -> i32 {
+    if y_vel > 0 {
+        y_vel * (y_vel + 1) / 2
+    } else {
+        0
+    }
+}
 
-        if curr_y > y_max {
-            y_max = curr_y;
+fn x_steps(x_vel: i32, area: &Area) -> Option<StepRange> {
+    let mut x = 0;
+    let mut vel = x_vel;
+    let mut range: Option<StepRange> = None;
+
+    for step in 1.. {
+        x += vel;
+        vel = apply_drag(vel);
+        let inside = in_range(x, area.x_start, area.x_end);
+
+        if inside {
+            let first = range.map_or(step, |r| r.first);
+            range = Some(StepRange {
+                first,
+                last: Some(step),
+            });
         }
 
-        if is_in_area(area, &probe.pos) {
-            break;
+        if vel == 0 {
+            return if inside {
+                range.map(|r| StepRange { last: None, ..r })
+            } else {
+                range
+            };
         }
 
-        if ngmi(area, &probe.pos) {
-            return None;
+        if range.is_some() && !inside {
+            break;
         }
     }
 
-    Some(y_max)
+    range
 }
 
-fn find_max_vel(area: &Area) -> ((i32, i32), i32) {
-    let x_bound = area.x_start;
-    let y_bound = 1000;
-    let mut y_max = 0;
-    let mut velocities = (0, 0);
-
-    for (x_vel, y_vel) in (1..x_bound).cartesian_product(1..y_bound) {
-        let y = find_max_y(x_vel, y_vel, area).unwrap_or(0);
-        if y > y_max {
-            y_max = y;
-            velocities = (x_vel, y_vel)
+fn y_steps(y_vel: i32, area: &Area) -> Vec<StepRange> {
+    let mut y = 0;
+    let mut vel = y_vel;
+    let mut ranges: Vec<StepRange> = vec![];
+    let mut current: Option<StepRange> = None;
+    let mut step = 0;
+
+    while vel > 0 || y >= area.y_end {
+        step += 1;
+        y += vel;
+        vel = apply_gravity(vel);
+
+        if in_range(y, area.y_end, area.y_start) {
+            let first = current.map_or(step, |r| r.first);
+            current = Some(StepRange {
+                first,
+                last: Some(step),
+            });
+        } else if let Some(range) = current.take() {
+            ranges.push(range);
         }
     }
+    ranges.extend(current);
 
-    (velocities, y_max)
+    ranges
 }
 
-fn count_reachable(area: &Area) -> i32 {
-    let x_bound = 1000;
-    let y_bound = 1000;
-    let mut reachable = 0;
+pub fn solve(area: &Area) -> Result<Vec<Hit>, TrajectoryError> {
+    let x_ranges: Vec<(i32, StepRange)> = (area.x_start.min(0)..=area.x_end.max(0))
+        .filter_map(|x_vel| x_steps(x_vel, area).map(|r| (x_vel, r)))
+        .collect();
 
-    for (x_vel, y_vel) in (1..x_bound).cartesian_product(-y_bound..y_bound) {
-        if find_max_y(x_vel, y_vel, area).is_some() {
-            reachable += 1
+    let y_min = area.y_end.min(0);
+    let y_max = if area.y_end > 0 {
+        area.y_start
+    } else if area.y_start < 0 {
+        -area.y_end - 1
+    } else {
+        // launcher height is inside the band, every upward shot crosses it again at step 2v + 1
+        if x_ranges.iter().any(|(_, r)| r.last.is_none()) {
+            return Err(TrajectoryError::InfiniteVelocities);
         }
-    }
+        let last_step = x_ranges.iter().filter_map(|(_, r)| r.last).max();
+        area.y_start.max(last_step.unwrap_or(0))
+    };
+
+    let y_ranges: Vec<(i32, Vec<StepRange>)> = (y_min..=y_max)
+        .map(|y_vel| (y_vel, y_steps(y_vel, area)))
+        .filter(|(_, r)| !r.is_empty())
+        .collect();
+
+    let hits = x_ranges
+        .iter()
+        .flat_map(|(x_vel, x_range)| {
+            y_ranges
+                .iter()
+                .filter(move |(_, ranges)| ranges.iter().any(|r| r.overlaps(x_range)))
+                .map(move |&(y_vel, _)| Hit {
+                    x_vel: *x_vel,
+                    y_vel,
+                    apex: apex(y_vel),
+                })
+        })
+        .collect();
+
+    Ok(hits)
+}
+
+fn find_max_vel(area: &Area) -> ((i32, i32), i32) {
+    let hits = solve(area).unwrap_or_else(|e| panic!("{}", e));
+    hits.iter()
+        .rev()
+        .max_by_key(|hit| hit.apex)
+        .map(|hit| ((hit.x_vel, hit.y_vel), hit.apex))
+        .unwrap_or(((0, 0), 0))
+}
 
-    reachable
+fn count_reachable(area: &Area) -> i32 {
+    let hits = solve(area).unwrap_or_else(|e| panic!("{}", e));
+    hits.len() as i32
 }
 
 pub struct Area {
@@ -103,14 +182,6 @@ pub struct Area {
     y_end: i32,
 }
 
-fn is_in_area(area: &Area, position: &Position) -> bool {
-    in_range(position.x, area.x_start, area.x_end) && in_range(position.y, area.y_end, area.y_start)
-}
-
-fn ngmi(area: &Area, position: &Position) -> bool {
-    position.x > area.x_end || position.y < area.y_end
-}
-
 fn in_range(num: i32, from: i32, to: i32) -> bool {
     num >= from && num <= to
 }
@@ -121,6 +192,13 @@ pub fn run(input: &str) {
     println!("Result: {:?}", res);
     let count = count_reachable(&area);
     println!("Reachable: {}", count);
+    match solve(&area) {
+        Ok(hits) => {
+            let lowest = hits.iter().min_by_key(|hit| (hit.apex, hit.y_vel));
+            println!("Flattest hitting shot: {:?}", lowest);
+        }
+        Err(e) => println!("Cannot solve target: {}", e),
+    }
 }
 
 fn parse_area(input: &str) -> Area {
@@ -138,10 +216,10 @@ fn parse_area(input: &str) -> Area {
         .collect();
 
     Area {
-        x_start: x_tokens[0],
-        x_end: x_tokens[1],
-        y_start: y_tokens[1],
-        y_end: y_tokens[0],
+        x_start: x_tokens[0].min(x_tokens[1]),
+        x_end: x_tokens[0].max(x_tokens[1]),
+        y_start: y_tokens[0].max(y_tokens[1]),
+        y_end: y_tokens[0].min(y_tokens[1]),
     }
 }
 
@@ -149,6 +227,45 @@ fn parse_area(input: &str) -> Area {
 mod tests {
     use super::*;
 
+    fn find_hit(x_vel: i32, y_vel: i32, area: &Area) -> Option<Hit> {
+        solve(area)
+            .unwrap()
+            .into_iter()
+            .find(|hit| hit.x_vel == x_vel && hit.y_vel == y_vel)
+    }
+
+    fn simulate_hits(area: &Area, bound: i32) -> Vec<(i32, i32)> {
+        let mut hits = vec![];
+        for x_vel in -bound..=bound {
+            for y_vel in -bound..=bound {
+                let (mut x, mut y, mut vx, mut vy) = (0, 0, x_vel, y_vel);
+                for _ in 0..4 * bound {
+                    x += vx;
+                    y += vy;
+                    vx = apply_drag(vx);
+                    vy = apply_gravity(vy);
+                    if in_range(x, area.x_start, area.x_end)
+                        && in_range(y, area.y_end, area.y_start)
+                    {
+                        hits.push((x_vel, y_vel));
+                        break;
+                    }
+                }
+            }
+        }
+        hits
+    }
+
+    fn assert_matches_simulation(area: &Area) {
+        let mut solved: Vec<(i32, i32)> = solve(area)
+            .unwrap()
+            .iter()
+            .map(|hit| (hit.x_vel, hit.y_vel))
+            .collect();
+        solved.sort_unstable();
+        assert_eq!(solved, simulate_hits(area, 60));
+    }
+
     #[test]
     fn test_max_y() {
         let area = Area {
@@ -157,7 +274,7 @@ mod tests {
             y_start: -5,
             y_end: -10,
         };
-        let max = find_max_y(7, 2, &area).unwrap();
+        let max = find_hit(7, 2, &area).unwrap().apex;
         assert_eq!(max, 3);
     }
 
@@ -169,7 +286,7 @@ mod tests {
             y_start: -5,
             y_end: -10,
         };
-        let max = find_max_y(9, 0, &area).unwrap();
+        let max = find_hit(9, 0, &area).unwrap().apex;
         assert_eq!(max, 0);
     }
 
@@ -181,7 +298,7 @@ mod tests {
             y_start: -5,
             y_end: -10,
         };
-        let max = find_max_y(6, 3, &area).unwrap();
+        let max = find_hit(6, 3, &area).unwrap().apex;
         assert_eq!(max, 6);
     }
 
@@ -193,7 +310,7 @@ mod tests {
             y_start: -5,
             y_end: -10,
         };
-        let max = find_max_y(6, 9, &area).unwrap();
+        let max = find_hit(6, 9, &area).unwrap().apex;
         assert_eq!(max, 45);
     }
 
@@ -222,4 +339,38 @@ mod tests {
         let expected = 112;
         assert_eq!(max, expected);
     }
+
+    #[test]
+    fn test_target_behind_launcher() {
+        let area = parse_area("target area: x=-30..-20, y=-10..-5");
+        assert_matches_simulation(&area);
+        assert_eq!(find_max_vel(&area), ((-7, 9), 45));
+    }
+
+    #[test]
+    fn test_target_above_launcher() {
+        let area = parse_area("target area: x=10..20, y=5..15");
+        assert_matches_simulation(&area);
+        assert_eq!(find_hit(4, 16, &area), None);
+        assert_eq!(
+            find_hit(5, 4, &area),
+            Some(Hit {
+                x_vel: 5,
+                y_vel: 4,
+                apex: 10
+            })
+        );
+    }
+
+    #[test]
+    fn test_target_straddling_launcher_height() {
+        let area = parse_area("target area: x=22..26, y=-4..3");
+        assert_matches_simulation(&area);
+    }
+
+    #[test]
+    fn test_infinite_velocities() {
+        let area = parse_area("target area: x=20..30, y=-4..3");
+        assert_eq!(solve(&area), Err(TrajectoryError::InfiniteVelocities));
+    }
 }
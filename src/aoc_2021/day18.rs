@@ -1,330 +1,415 @@
-use itertools::Itertools;
+use std::fmt;
+use std::fmt::Formatter;
+use std::iter::Sum;
+use std::ops::Add;
+use std::str::FromStr;
 
-fn pair_values(num: &str, index: usize) -> (i32, i32) {
-    let pair_str: String = num
-        .chars()
-        .skip(index + 1)
-        .take_while(|&c| c != ']')
-        .collect();
+pub fn run(input: &[String]) {
+    let nums = match parse_nums(input) {
+        Ok(nums) => nums,
+        Err(e) => panic!("Invalid snailfish number: {}", e),
+    };
 
-    let values: Vec<_> = pair_str
-        .split(',')
-        .map(|p| p.parse::<i32>().unwrap())
-        .collect();
+    if let [first, second, ..] = nums.as_slice() {
+        let trace = first.add_traced(second);
+        println!("Reduction of the first addition:\n{}", trace);
+    }
 
-    (values[0], values[1])
-}
+    if let Some(final_sum) = nums.iter().cloned().sum::<Option<SnailNum>>() {
+        println!("Final sum: {}", final_sum);
+        println!("Sum magnitude: {}", final_sum.magnitude());
+    }
 
-pub fn run(input: &[String]) {
-    part_1(input);
-    let max_mag = part_2(input);
+    let max_mag = max_pair_magnitude(&nums);
     println!("Max magnitude: {}", max_mag);
 }
 
-fn part_1(input: &[String]) {
-    let final_sum = add_all(input);
-    println!("Final sum!: {}", final_sum);
-    let res = magnitude(&final_sum);
-    println!("Sum magnitude: {}", res);
+const EXPLODE_DEPTH: usize = 4;
+const SPLIT_THRESHOLD: u32 = 10;
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+struct Regular {
+    value: u32,
+    depth: usize,
 }
 
-fn part_2(input: &[String]) -> i32 {
-    input
-        .iter()
-        .permutations(2)
-        .map(|p| {
-            let left = p[0].to_string();
-            let right = p[1].to_string();
-            magnitude(&add_and_process(&left, &right))
-        })
-        .max()
-        .unwrap()
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct SnailNum {
+    regulars: Vec<Regular>,
 }
 
 #[derive(Debug, Copy, Clone, Eq, PartialEq)]
-pub struct PairInfo {
-    left: i32,
-    right: i32,
-    index: usize,
+pub enum Side {
+    Left,
+    Right,
 }
 
-impl PairInfo {
-    fn str_rep(&self) -> String {
-        format!("[{},{}]", self.left, self.right)
-    }
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum Action {
+    Explode(Vec<Side>),
+    Split(Vec<Side>),
 }
 
-fn leftmost_pair(num: &str, level: usize) -> Option<PairInfo> {
-    leftmost_lvl_index(num, level).map(|index| {
-        let (left, right) = pair_values(num, index);
-        PairInfo { left, right, index }
-    })
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct ReductionStep {
+    pub action: Action,
+    pub value: SnailNum,
 }
 
-fn can_explode(num: &str) -> bool {
-    leftmost_pair(num, 4).is_some()
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct ReductionTrace {
+    pub sum: SnailNum,
+    pub steps: Vec<ReductionStep>,
 }
 
-fn explode(num: &str) -> String {
-    println!("Exploding: {}", num);
-    match leftmost_pair(num, 4) {
-        None => {
-            println!("no lvl 4 pair for : {}", num);
+#[derive(Debug, Eq, PartialEq)]
+pub enum ParseSnailNumError {
+    UnexpectedChar(usize, char),
+    UnexpectedEnd,
+    NotAPair,
+}
 
-            num.to_string()
-        }
-        Some(pair) => {
-            let start = pair.index;
-            let end = start + num.chars().skip(start).take_while(|&c| c != ']').count();
-            let can_right = can_explode_right(num, end);
-            let can_left = can_explode_left(num, start);
-
-            if can_right && can_left {
-                // println!("Can explode pair: {} both ways", pair.to_string());
-                let (left_part, after_left) = left_part(num, pair, start);
-                let (right_part, after_right) = right_part(num, pair, end);
-                // println!("After left: {} After right: {}", after_left, after_right);
-
-                let to_replace =
-                    format!("{}[{},{}]{}", left_part, pair.left, pair.right, right_part);
-                let replace_with = format!("{}0{}", after_left, after_right);
-                // println!("To replace: {} with {}", to_replace, replace_with);
-
-                return num.replacen(&to_replace, &replace_with, 1);
-            }
+impl SnailNum {
+    pub fn reduce(&mut self) {
+        while self.explode() || self.split() {}
+    }
 
-            if can_right {
-                explode_right(num, pair, end)
-            } else {
-                explode_left(num, pair, start)
-            }
+    pub fn add_traced(&self, rhs: &SnailNum) -> ReductionTrace {
+        let sum = self.concat(rhs);
+        let mut current = sum.clone();
+        let mut steps: Vec<ReductionStep> = vec![];
+
+        while let Some(action) = current.reduce_step() {
+            steps.push(ReductionStep {
+                action,
+                value: current.clone(),
+            });
         }
+
+        ReductionTrace { sum, steps }
     }
-}
 
-fn left_part(num: &str, pair: PairInfo, start: usize) -> (String, String) {
-    let part = num
-        .chars()
-        .rev()
-        .skip(num.len() - start)
-        .take_while(|c| !c.is_ascii_digit())
-        .collect::<String>();
+    fn reduce_step(&mut self) -> Option<Action> {
+        if let Some(idx) = self.explode_target() {
+            let mut path = self.leaf_path(idx);
+            path.pop();
+            self.explode_at(idx);
+            return Some(Action::Explode(path));
+        }
 
-    let num_str = num
-        .chars()
-        .rev()
-        .skip(num.len() - start + part.len())
-        .take_while(|c| c.is_ascii_digit())
-        .collect::<String>();
+        if let Some(idx) = self.split_target() {
+            let path = self.leaf_path(idx);
+            self.split_at(idx);
+            return Some(Action::Split(path));
+        }
 
-    let rev_part: String = part.chars().rev().collect();
-    let rev_num_str: String = num_str.chars().rev().collect();
+        None
+    }
 
-    let left_num = rev_num_str.parse::<i32>().unwrap();
-    let new_num = left_num + pair.left;
+    fn leaf_path(&self, idx: usize) -> Vec<Side> {
+        let mut path: Vec<Side> = vec![];
 
-    // first new number, then old part
-    let before = format!("{}{}", rev_num_str, rev_part);
-    let after = format!("{}{}", new_num, rev_part);
+        for (i, regular) in self.regulars.iter().take(idx + 1).enumerate() {
+            if i > 0 {
+                while path.last() == Some(&Side::Right) {
+                    path.pop();
+                }
+                path.pop();
+                path.push(Side::Right);
+            }
+            path.resize(regular.depth, Side::Left);
+        }
 
-    (before, after)
-}
+        path
+    }
+
+    fn concat(&self, rhs: &SnailNum) -> SnailNum {
+        let regulars = self
+            .regulars
+            .iter()
+            .chain(rhs.regulars.iter())
+            .map(|r| Regular {
+                value: r.value,
+                depth: r.depth + 1,
+            })
+            .collect();
+
+        SnailNum { regulars }
+    }
+
+    pub fn magnitude(&self) -> u32 {
+        let mut stack: Vec<Regular> = Vec::with_capacity(self.regulars.len());
+
+        for regular in &self.regulars {
+            let mut current = *regular;
+            while let Some(left) = stack.last() {
+                if left.depth != current.depth {
+                    break;
+                }
+                current = Regular {
+                    value: 3 * left.value + 2 * current.value,
+                    depth: current.depth - 1,
+                };
+                stack.pop();
+            }
+            stack.push(current);
+        }
 
-fn right_part(num: &str, pair: PairInfo, end: usize) -> (String, String) {
-    let part = num
-        .chars()
-        .skip(end)
-        .take_while(|c| !c.is_ascii_digit())
-        .collect::<String>();
-    // println!("Part: {}", part);
-    let num_str = num
-        .chars()
-        .skip(end + part.len())
-        .take_while(|c| c.is_ascii_digit())
-        .collect::<String>();
-    let num: i32 = num_str.parse().unwrap();
-    let after = format!("{}{}", &part[1..], num + pair.right);
-    (format!("{}{}", &part[1..], num_str), after)
-}
+        stack.iter().map(|r| r.value).sum()
+    }
 
-fn split(num: &str) -> String {
-    split_num(num, find_split(num).unwrap())
-}
+    fn explode_target(&self) -> Option<usize> {
+        // Leftmost pair of regulars nested deeper than EXPLODE_DEPTH. Two neighbouring
+        // regulars at the same depth further right could be cousins, but any such
+        // cousin is preceded by a sibling pair that is found first.
+        self.regulars
+            .windows(2)
+            .position(|pair| pair[0].depth > EXPLODE_DEPTH && pair[0].depth == pair[1].depth)
+    }
 
-fn split_num(num: &str, split: i32) -> String {
-    let half = (split as f64) / 2.0;
-    let left = half.floor() as i32;
-    let right = half.ceil() as i32;
-    let to_replace = format!("{}", split);
-    let replace_with = format!("[{},{}]", left, right);
-    num.replacen(&to_replace, &replace_with, 1)
-}
+    fn explode(&mut self) -> bool {
+        match self.explode_target() {
+            Some(idx) => {
+                self.explode_at(idx);
+                true
+            }
+            None => false,
+        }
+    }
 
-fn add(left: &str, right: &str) -> String {
-    format!("[{},{}]", left, right)
-}
+    fn explode_at(&mut self, idx: usize) {
+        let left = self.regulars[idx];
+        let right = self.regulars.remove(idx + 1);
 
-fn add_all(pairs: &[String]) -> String {
-    let mut curr = pairs.iter().next().unwrap().clone();
-    for pair in pairs.iter().skip(1) {
-        curr = add_and_process(&curr, pair)
+        if idx > 0 {
+            self.regulars[idx - 1].value += left.value;
+        }
+        if let Some(next) = self.regulars.get_mut(idx + 1) {
+            next.value += right.value;
+        }
+        self.regulars[idx] = Regular {
+            value: 0,
+            depth: left.depth - 1,
+        };
     }
-    curr
-}
 
-fn add_and_process(left: &str, right: &str) -> String {
-    process(&add(left, right))
-}
+    fn split_target(&self) -> Option<usize> {
+        self.regulars
+            .iter()
+            .position(|r| r.value >= SPLIT_THRESHOLD)
+    }
 
-fn process(num: &str) -> String {
-    println!("Processing : {}", num);
-    let mut curr_num = num.to_string();
-    loop {
-        if can_explode(&curr_num) {
-            curr_num = explode(&curr_num);
-            println!("After explode : {}", curr_num);
-            continue;
+    fn split(&mut self) -> bool {
+        match self.split_target() {
+            Some(idx) => {
+                self.split_at(idx);
+                true
+            }
+            None => false,
         }
+    }
 
-        if can_split(&curr_num) {
-            curr_num = split(&curr_num);
-            println!("After split : {}", curr_num);
-            continue;
+    fn split_at(&mut self, idx: usize) {
+        let Regular { value, depth } = self.regulars[idx];
+        let left = Regular {
+            value: value / 2,
+            depth: depth + 1,
+        };
+        let right = Regular {
+            value: value - value / 2,
+            depth: depth + 1,
+        };
+        self.regulars[idx] = left;
+        self.regulars.insert(idx + 1, right);
+    }
+
+    fn fmt_element(&self, idx: &mut usize, depth: usize, f: &mut Formatter<'_>) -> fmt::Result {
+        let regular = self.regulars[*idx];
+        if regular.depth == depth {
+            *idx += 1;
+            return write!(f, "{}", regular.value);
         }
-        println!("No actions left, result: {}", curr_num);
-        break;
+
+        write!(f, "[")?;
+        self.fmt_element(idx, depth + 1, f)?;
+        write!(f, ",")?;
+        self.fmt_element(idx, depth + 1, f)?;
+        write!(f, "]")
     }
-    curr_num
 }
 
-fn can_split(num: &str) -> bool {
-    find_split(num).is_some()
+impl fmt::Display for SnailNum {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        self.fmt_element(&mut 0, 0, f)
+    }
 }
 
-fn find_split(num: &str) -> Option<i32> {
-    let mut digits = "".to_string();
-    for c in num.chars() {
-        if c.is_ascii_digit() {
-            digits.push_str(&c.to_string())
-        } else {
-            digits = "".to_string();
-        }
-
-        if digits.len() > 1 {
-            return Some(digits.parse().unwrap());
+impl Action {
+    // sides taken from the root to the exploded pair or the split regular
+    pub fn path(&self) -> &[Side] {
+        match self {
+            Action::Explode(path) | Action::Split(path) => path,
         }
     }
-
-    None
 }
 
-fn can_explode_right(num: &str, pair_end: usize) -> bool {
-    num.chars().skip(pair_end).any(|c| c != ']')
+impl fmt::Display for Action {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Action::Explode(_) => "explode",
+            Action::Split(_) => "split",
+        };
+
+        write!(f, "{} at ", name)?;
+        for side in self.path() {
+            match side {
+                Side::Left => write!(f, "L")?,
+                Side::Right => write!(f, "R")?,
+            }
+        }
+        Ok(())
+    }
 }
 
-fn can_explode_left(num: &str, pair_start: usize) -> bool {
-    num.chars()
-        .rev()
-        .skip(num.len() - (pair_start - 1))
-        .any(|c| c != '[')
+impl fmt::Display for ReductionTrace {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "after addition: {}", self.sum)?;
+        for step in &self.steps {
+            write!(f, "\nafter {}: {}", step.action, step.value)?;
+        }
+        Ok(())
+    }
 }
 
-fn explode_left(num: &str, pair: PairInfo, start: usize) -> String {
-    let left_num_str = num
-        .chars()
-        .rev()
-        .skip(num.len() - (start - 1))
-        .take_while(|&c| c != '[')
-        .collect::<String>();
-
-    let rev = left_num_str.chars().rev().collect::<String>();
-    let left_num = rev.parse::<i32>().unwrap();
-
-    let new_left = left_num + pair.left;
-    let to_replace = format!("[{},[{},{}]]", left_num, pair.left, pair.right);
-    let replace_with = format!("[{},0]", new_left);
-    num.replacen(&to_replace, &replace_with, 1)
+impl fmt::Display for ParseSnailNumError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseSnailNumError::UnexpectedChar(pos, c) => {
+                write!(f, "unexpected '{}' at position {}", c, pos)
+            }
+            ParseSnailNumError::UnexpectedEnd => write!(f, "unexpected end of input"),
+            ParseSnailNumError::NotAPair => write!(f, "snailfish number must be a pair"),
+        }
+    }
 }
 
-fn explode_right(num: &str, pair: PairInfo, end: usize) -> String {
-    // println!("Exploding to the right: {} {}", num, pair.to_string());
-    let (before, after) = right_part(num, pair, end);
-    // println!("Replacing {} with {}", before, after);
-    let to_replace = format!("{}{}", pair.str_rep(), before);
-    let replace_with = format!("0{}", after);
-    num.replacen(&to_replace, &replace_with, 1)
+struct Parser<'a> {
+    chars: std::iter::Peekable<std::str::CharIndices<'a>>,
+    regulars: Vec<Regular>,
 }
 
-fn leftmost_lvl_index(num: &str, level: usize) -> Option<usize> {
-    let mut open_count = 0;
-    for (idx, c) in num.chars().enumerate() {
-        match c {
-            '[' => open_count += 1,
-            ']' => open_count -= 1,
-            _ => {}
+impl<'a> Parser<'a> {
+    fn expect(&mut self, expected: char) -> Result<(), ParseSnailNumError> {
+        match self.chars.next() {
+            Some((_, c)) if c == expected => Ok(()),
+            Some((pos, c)) => Err(ParseSnailNumError::UnexpectedChar(pos, c)),
+            None => Err(ParseSnailNumError::UnexpectedEnd),
         }
-        if open_count == level + 1 {
-            return Some(idx);
+    }
+
+    fn element(&mut self, depth: usize) -> Result<(), ParseSnailNumError> {
+        match self.chars.peek() {
+            Some((_, '[')) => {
+                self.expect('[')?;
+                self.element(depth + 1)?;
+                self.expect(',')?;
+                self.element(depth + 1)?;
+                self.expect(']')
+            }
+            Some((_, c)) if c.is_ascii_digit() => self.regular(depth),
+            Some((pos, c)) => Err(ParseSnailNumError::UnexpectedChar(*pos, *c)),
+            None => Err(ParseSnailNumError::UnexpectedEnd),
         }
     }
 
-    None
-}
+    fn regular(&mut self, depth: usize) -> Result<(), ParseSnailNumError> {
+        let mut value: u32 = 0;
 
-fn find_pivot_index(num: &str) -> usize {
-    let mut open_count = num.chars().take_while(|&c| c == '[').count();
+        while let Some(&(pos, c)) = self.chars.peek() {
+            let digit = match c.to_digit(10) {
+                Some(digit) => digit,
+                None => break,
+            };
+            value = value
+                .checked_mul(10)
+                .and_then(|v| v.checked_add(digit))
+                .ok_or(ParseSnailNumError::UnexpectedChar(pos, c))?;
+            self.chars.next();
+        }
 
-    if open_count == 0 {
-        return 0;
+        self.regulars.push(Regular { value, depth });
+        Ok(())
     }
+}
 
-    let mut close_count = 0;
+impl FromStr for SnailNum {
+    type Err = ParseSnailNumError;
 
-    for (idx, c) in num.chars().enumerate().skip(open_count) {
-        if close_count == open_count - 1 && c == ',' {
-            return idx;
-        }
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parser = Parser {
+            chars: s.trim().char_indices().peekable(),
+            regulars: vec![],
+        };
 
-        if idx != 0 {
-            match c {
-                '[' => open_count += 1,
-                ']' => close_count += 1,
-                _ => {}
-            }
+        parser.element(0)?;
+        if let Some((pos, c)) = parser.chars.next() {
+            return Err(ParseSnailNumError::UnexpectedChar(pos, c));
+        }
+        if parser.regulars.len() < 2 {
+            return Err(ParseSnailNumError::NotAPair);
         }
-    }
 
-    0
+        Ok(SnailNum {
+            regulars: parser.regulars,
+        })
+    }
 }
 
-fn magnitude(num: &str) -> i32 {
-    if num.is_empty() {
-        return 0;
+impl<'a> Add<&'a SnailNum> for &'a SnailNum {
+    type Output = SnailNum;
+
+    fn add(self, rhs: &'a SnailNum) -> SnailNum {
+        let mut sum = self.concat(rhs);
+        sum.reduce();
+        sum
     }
-    let pivot_index = find_pivot_index(num);
-    let mut stripped = "";
+}
 
-    if pivot_index != 0 {
-        stripped = &num[1..num.len() - 1];
+impl Add for SnailNum {
+    type Output = SnailNum;
+
+    fn add(self, rhs: SnailNum) -> SnailNum {
+        &self + &rhs
     }
+}
 
-    let (left_str, mut right_str) = stripped.split_at(pivot_index - 1);
+// snailfish addition has no identity element, so an empty sum is None
+impl Sum<SnailNum> for Option<SnailNum> {
+    fn sum<I: Iterator<Item = SnailNum>>(iter: I) -> Option<SnailNum> {
+        iter.reduce(|acc, num| acc + num)
+    }
+}
 
-    right_str = &right_str[1..];
-    let left = left_str.parse::<i32>();
-    let right = right_str.parse::<i32>();
+fn parse_nums(input: &[String]) -> Result<Vec<SnailNum>, ParseSnailNumError> {
+    input
+        .iter()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| line.parse())
+        .collect()
+}
 
-    let left_res = match left {
-        Ok(res) => 3 * res,
-        Err(_) => 3 * magnitude(left_str),
-    };
+fn max_pair_magnitude(nums: &[SnailNum]) -> u32 {
+    let mut max_mag = 0;
 
-    let right_res = match right {
-        Ok(res) => 2 * res,
-        Err(_) => 2 * magnitude(right_str),
-    };
+    for (i, left) in nums.iter().enumerate() {
+        for (j, right) in nums.iter().enumerate() {
+            if i != j {
+                max_mag = max_mag.max((left + right).magnitude());
+            }
+        }
+    }
 
-    left_res + right_res
+    max_mag
 }
 
 #[cfg(test)]
@@ -332,154 +417,232 @@ mod tests {
     use super::*;
     use crate::common::parse::test_utils::vec_of_strings;
 
-    #[test]
-    fn test_lvl_index() {
-        let input = "[[[[[9,8],1],2],3],4]";
-        let idx = leftmost_lvl_index(input, 4).unwrap();
-        pair_values(input, idx);
-        assert_eq!(idx, 4)
+    fn snail(input: &str) -> SnailNum {
+        input.parse().unwrap()
+    }
+
+    fn exploded(input: &str) -> String {
+        let mut num = snail(input);
+        num.explode();
+        num.to_string()
+    }
+
+    fn add_all(input: &[String]) -> String {
+        parse_nums(input)
+            .unwrap()
+            .into_iter()
+            .sum::<Option<SnailNum>>()
+            .unwrap()
+            .to_string()
     }
 
     #[test]
-    fn test_pair_values_1() {
-        let input = "[[[[[9,8],1],2],3],4]";
-        let values = pair_values(input, 4);
-        assert_eq!((9, 8), values)
+    fn test_parse_and_display_roundtrip() {
+        let input = "[[[[1,3],[5,3]],[[1,3],[8,7]]],[[[4,9],[6,9]],[[8,2],[7,3]]]]";
+        assert_eq!(snail(input).to_string(), input);
     }
 
     #[test]
-    fn test_leftmost_level_1() {
-        let input = "[[[[[9,8],1],2],3],4]";
-        let values = leftmost_pair(input, 4).unwrap();
+    fn test_parse_rejects_malformed() {
         assert_eq!(
-            PairInfo {
-                left: 9,
-                right: 8,
-                index: 4
-            },
-            values
-        )
+            "[1,2".parse::<SnailNum>(),
+            Err(ParseSnailNumError::UnexpectedEnd)
+        );
+        assert_eq!(
+            "[1;2]".parse::<SnailNum>(),
+            Err(ParseSnailNumError::UnexpectedChar(2, ';'))
+        );
+        assert_eq!(
+            "[1,2]]".parse::<SnailNum>(),
+            Err(ParseSnailNumError::UnexpectedChar(5, ']'))
+        );
+        assert_eq!(
+            "[[1,x],2]".parse::<SnailNum>(),
+            Err(ParseSnailNumError::UnexpectedChar(4, 'x'))
+        );
+        assert_eq!("7".parse::<SnailNum>(), Err(ParseSnailNumError::NotAPair));
     }
 
     #[test]
-    fn test_leftmost_level_2() {
-        let input = "[7,[6,[5,[4,[3,2]]]]]";
-        let values = leftmost_pair(input, 4).unwrap();
-        assert_eq!(
-            PairInfo {
-                left: 3,
-                right: 2,
-                index: 12
-            },
-            values
-        )
+    fn test_explode_target_1() {
+        let input = "[[[[[9,8],1],2],3],4]";
+        assert_eq!(snail(input).explode_target(), Some(0));
     }
 
     #[test]
-    fn test_leftmost_level_3() {
-        let input = "[[3,[2,[1,[7,3]]]],[6,[5,[4,[3,2]]]]]";
-        let values = leftmost_pair(input, 4).unwrap();
-        assert_eq!(
-            PairInfo {
-                left: 7,
-                right: 3,
-                index: 10
-            },
-            values
-        )
+    fn test_explode_target_2() {
+        let input = "[7,[6,[5,[4,[3,2]]]]]";
+        assert_eq!(snail(input).explode_target(), Some(4));
     }
 
     #[test]
-    fn test_can_explode_right() {
-        assert_eq!(can_explode_right("[[6,[5,[4,[3,2]]]],1]", 14), true);
-        assert_eq!(can_explode_right("[7,[6,[5,[4,[3,2]]]]]", 16), false);
+    fn test_explode_target_3() {
+        let input = "[[3,[2,[1,[7,3]]]],[6,[5,[4,[3,2]]]]]";
+        assert_eq!(snail(input).explode_target(), Some(3));
     }
 
     #[test]
-    fn test_can_explode_left() {
-        assert_eq!(can_explode_left("[[[[[9,8],1],2],3],4]", 4), false);
-        assert_eq!(can_explode_left("[[6,[5,[4,[3,2]]]],1]", 11), true);
+    fn test_explode_target_4() {
+        let input = "[[[[0,[1,2]],[[3,4],5]],6],7]";
+        assert_eq!(snail(input).explode_target(), Some(1));
     }
 
     #[test]
     fn test_explode_pair_right() {
         let input = "[[[[[9,8],1],2],3],4]";
-        let exploded = explode(input);
-        assert_eq!(exploded, "[[[[0,9],2],3],4]")
+        assert_eq!(exploded(input), "[[[[0,9],2],3],4]")
     }
 
     #[test]
     fn test_explode_pair_right_2() {
         let input = "[[6,[5,[4,[3,2]]]],1]";
-        let exploded = explode(input);
-        assert_eq!(exploded, "[[6,[5,[7,0]]],3]")
+        assert_eq!(exploded(input), "[[6,[5,[7,0]]],3]")
     }
 
     #[test]
     fn test_explode_pair_left() {
         let input = "[7,[6,[5,[4,[3,2]]]]]";
-        let exploded = explode(input);
-        assert_eq!(exploded, "[7,[6,[5,[7,0]]]]")
+        assert_eq!(exploded(input), "[7,[6,[5,[7,0]]]]")
     }
 
     #[test]
     fn test_explode_multiple_lvl_4() {
         let input = "[[3,[2,[1,[7,3]]]],[6,[5,[4,[3,2]]]]]";
-        let exploded = explode(input);
-        assert_eq!(exploded, "[[3,[2,[8,0]]],[9,[5,[4,[3,2]]]]]")
+        assert_eq!(exploded(input), "[[3,[2,[8,0]]],[9,[5,[4,[3,2]]]]]")
     }
 
     #[test]
     fn test_explode_multiple_2_explodes() {
         let input = "[[3,[2,[8,0]]],[9,[5,[4,[3,2]]]]]";
-        let exploded = explode(input);
-        assert_eq!(exploded, "[[3,[2,[8,0]]],[9,[5,[7,0]]]]")
+        assert_eq!(exploded(input), "[[3,[2,[8,0]]],[9,[5,[7,0]]]]")
     }
 
     #[test]
     fn test_explode_2_deep_4_me() {
         let input = "[[[[0,7],4],[7,[[8,4],9]]],[1,1]]";
-        let exploded = explode(input);
-        assert_eq!(exploded, "[[[[0,7],4],[15,[0,13]]],[1,1]]")
+        assert_eq!(exploded(input), "[[[[0,7],4],[15,[0,13]]],[1,1]]")
     }
 
     #[test]
     fn test_explode_addition() {
         let input = "[[[[[1,1],[2,2]],[3,3]],[4,4]],[5,5]]";
-        let exploded = explode(input);
-        assert_eq!(exploded, "[[[[0,[3,2]],[3,3]],[4,4]],[5,5]]")
+        assert_eq!(exploded(input), "[[[[0,[3,2]],[3,3]],[4,4]],[5,5]]")
     }
 
     #[test]
-    fn test_find_split() {
+    fn test_split_target() {
         let input = "[[3,[2,[10,11]]],[9,[5,[4,[3,2]]]]]";
-        let exploded = find_split(input).unwrap();
-        assert_eq!(exploded, 10)
+        let num = snail(input);
+        let idx = num.split_target().unwrap();
+        assert_eq!(num.regulars[idx].value, 10)
     }
 
     #[test]
     fn test_split() {
-        let input = "[11,0]";
-        let exploded = split_num(input, 11);
-        assert_eq!(exploded, "[[5,6],0]")
+        let mut num = snail("[11,0]");
+        num.split();
+        assert_eq!(num.to_string(), "[[5,6],0]")
     }
 
     #[test]
     fn test_can_split() {
         let input = "[[[[0,7],4],[15,[0,13]]],[1,1]]";
-        assert_eq!(can_split(input), true);
+        assert!(snail(input).split_target().is_some());
+    }
+
+    #[test]
+    fn test_reduce() {
+        let mut num = snail("[[[[[4,3],4],4],[7,[[8,4],9]]],[1,1]]");
+        num.reduce();
+        assert_eq!(num.to_string(), "[[[[0,7],4],[[7,8],[6,0]]],[8,1]]")
+    }
+
+    #[test]
+    fn test_add() {
+        let sum = snail("[[[[4,3],4],4],[7,[[8,4],9]]]") + snail("[1,1]");
+        assert_eq!(sum.to_string(), "[[[[0,7],4],[[7,8],[6,0]]],[8,1]]")
     }
 
     #[test]
-    fn test_process() {
-        let input = "[[[[[4,3],4],4],[7,[[8,4],9]]],[1,1]]";
-        assert_eq!(process(input), "[[[[0,7],4],[[7,8],[6,0]]],[8,1]]")
+    fn test_leaf_path() {
+        let num = snail("[[1,[2,3]],[[4,5],6]]");
+        let paths: Vec<Vec<Side>> = (0..6).map(|i| num.leaf_path(i)).collect();
+
+        use Side::{Left as L, Right as R};
+        assert_eq!(
+            paths,
+            vec![
+                vec![L, L],
+                vec![L, R, L],
+                vec![L, R, R],
+                vec![R, L, L],
+                vec![R, L, R],
+                vec![R, R]
+            ]
+        );
     }
 
     #[test]
-    fn test_end_pr() {
-        let input = "[[[[[4,3],4],4],[7,[[8,4],9]]],[1,1]]";
-        assert_eq!(process(input), "[[[[0,7],4],[[7,8],[6,0]]],[8,1]]")
+    fn test_add_traced() {
+        // given
+        let left = snail("[[[[4,3],4],4],[7,[[8,4],9]]]");
+        let right = snail("[1,1]");
+
+        // when
+        let trace = left.add_traced(&right);
+
+        // then
+        use Side::{Left as L, Right as R};
+        let step = |action: Action, value: &str| ReductionStep {
+            action,
+            value: snail(value),
+        };
+        let expected = vec![
+            step(
+                Action::Explode(vec![L, L, L, L]),
+                "[[[[0,7],4],[7,[[8,4],9]]],[1,1]]",
+            ),
+            step(
+                Action::Explode(vec![L, R, R, L]),
+                "[[[[0,7],4],[15,[0,13]]],[1,1]]",
+            ),
+            step(
+                Action::Split(vec![L, R, L]),
+                "[[[[0,7],4],[[7,8],[0,13]]],[1,1]]",
+            ),
+            step(
+                Action::Split(vec![L, R, R, R]),
+                "[[[[0,7],4],[[7,8],[0,[6,7]]]],[1,1]]",
+            ),
+            step(
+                Action::Explode(vec![L, R, R, R]),
+                "[[[[0,7],4],[[7,8],[6,0]]],[8,1]]",
+            ),
+        ];
+        assert_eq!(
+            trace.sum.to_string(),
+            "[[[[[4,3],4],4],[7,[[8,4],9]]],[1,1]]"
+        );
+        assert_eq!(trace.steps, expected);
+        assert_eq!(trace.steps.last().unwrap().value, &left + &right);
+        assert_eq!(trace.steps[2].action.path(), &[L, R, L]);
+    }
+
+    #[test]
+    fn test_display_trace() {
+        let trace = snail("[[[[4,3],4],4],[7,[[8,4],9]]]").add_traced(&snail("[1,1]"));
+        let lines: Vec<String> = trace.to_string().lines().map(String::from).collect();
+        assert_eq!(
+            lines,
+            vec_of_strings![
+                "after addition: [[[[[4,3],4],4],[7,[[8,4],9]]],[1,1]]",
+                "after explode at LLLL: [[[[0,7],4],[7,[[8,4],9]]],[1,1]]",
+                "after explode at LRRL: [[[[0,7],4],[15,[0,13]]],[1,1]]",
+                "after split at LRL: [[[[0,7],4],[[7,8],[0,13]]],[1,1]]",
+                "after split at LRRR: [[[[0,7],4],[[7,8],[0,[6,7]]]],[1,1]]",
+                "after explode at LRRR: [[[[0,7],4],[[7,8],[6,0]]],[8,1]]"
+            ]
+        );
     }
 
     #[test]
@@ -540,22 +703,25 @@ mod tests {
         )
     }
 
+    #[test]
+    fn test_sum_of_nothing_is_none() {
+        let nums: Vec<SnailNum> = vec![];
+        assert_eq!(nums.into_iter().sum::<Option<SnailNum>>(), None);
+    }
+
     #[test]
     fn test_magnitude() {
-        let res = magnitude("[5,8]");
-        assert_eq!(res, 31);
+        assert_eq!(snail("[5,8]").magnitude(), 31);
     }
 
     #[test]
     fn test_magnitude_2() {
-        let res = magnitude("[[9,1],[1,9]]");
-        assert_eq!(res, 129);
+        assert_eq!(snail("[[9,1],[1,9]]").magnitude(), 129);
     }
 
     #[test]
     fn test_magnitude_3() {
-        let res = magnitude("[[1,2],[[3,4],5]]");
-        assert_eq!(res, 143);
+        assert_eq!(snail("[[1,2],[[3,4],5]]").magnitude(), 143);
     }
 
     #[test]
@@ -572,7 +738,7 @@ mod tests {
             "[[2,[[7,7],7]],[[5,8],[[9,3],[0,2]]]]",
             "[[[[5,2],5],[8,[3,7]]],[[5,[7,5]],[4,4]]]"
         ];
-        let res = part_2(&input);
-        assert_eq!(res, 3993);
+        let nums = parse_nums(&input).unwrap();
+        assert_eq!(max_pair_magnitude(&nums), 3993);
     }
 }
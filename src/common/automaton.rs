@@ -0,0 +1,307 @@
+use std::collections::VecDeque;
+
+const MOORE: [(i32, i32); 8] = [
+    (-1, -1),
+    (-1, 0),
+    (-1, 1),
+    (0, -1),
+    (0, 1),
+    (1, -1),
+    (1, 0),
+    (1, 1),
+];
+const VON_NEUMANN: [(i32, i32); 4] = [(-1, 0), (0, -1), (0, 1), (1, 0)];
+
+pub trait Neighbourhood {
+    fn offsets(&self) -> &[(i32, i32)];
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Moore;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct VonNeumann;
+
+impl Neighbourhood for Moore {
+    fn offsets(&self) -> &[(i32, i32)] {
+        &MOORE
+    }
+}
+
+impl Neighbourhood for VonNeumann {
+    fn offsets(&self) -> &[(i32, i32)] {
+        &VON_NEUMANN
+    }
+}
+
+impl Neighbourhood for Vec<(i32, i32)> {
+    fn offsets(&self) -> &[(i32, i32)] {
+        self
+    }
+}
+
+pub trait Rule {
+    type Cell: Copy;
+
+    // synchronous update, every cell sees its neighbours from the previous generation
+    fn update(&self, cell: Self::Cell, neighbours: &[Self::Cell]) -> Self::Cell;
+
+    fn fires(&self, _cell: Self::Cell) -> bool {
+        false
+    }
+
+    // applied once per fired neighbour during the cascade
+    fn excite(&self, cell: Self::Cell) -> Self::Cell {
+        cell
+    }
+
+    fn reset(&self, cell: Self::Cell) -> Self::Cell {
+        cell
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ThresholdRule {
+    pub increment: i32,
+    pub threshold: i32,
+    pub reset_value: i32,
+}
+
+impl Rule for ThresholdRule {
+    type Cell = i32;
+
+    fn update(&self, cell: i32, _neighbours: &[i32]) -> i32 {
+        cell + self.increment
+    }
+
+    fn fires(&self, cell: i32) -> bool {
+        cell > self.threshold
+    }
+
+    fn excite(&self, cell: i32) -> i32 {
+        cell + 1
+    }
+
+    fn reset(&self, _cell: i32) -> i32 {
+        self.reset_value
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct StepEvent {
+    pub step: usize,
+    pub fired: Vec<(i32, i32)>,
+}
+
+pub struct Automaton<R: Rule, N: Neighbourhood> {
+    width: i32,
+    height: i32,
+    cells: Vec<R::Cell>,
+    buffer: Vec<R::Cell>,
+    neighbourhood: N,
+    rule: R,
+    steps: usize,
+}
+
+pub struct Steps<'a, R: Rule, N: Neighbourhood> {
+    automaton: &'a mut Automaton<R, N>,
+}
+
+impl<R: Rule, N: Neighbourhood> Automaton<R, N> {
+    pub fn new(
+        cells: Vec<R::Cell>,
+        width: i32,
+        height: i32,
+        neighbourhood: N,
+        rule: R,
+    ) -> Automaton<R, N> {
+        if cells.len() != (width * height) as usize {
+            panic!(
+                "Expected {} cells for a {}x{} grid, got {}",
+                width * height,
+                width,
+                height,
+                cells.len()
+            );
+        }
+
+        Automaton {
+            width,
+            height,
+            buffer: cells.clone(),
+            cells,
+            neighbourhood,
+            rule,
+            steps: 0,
+        }
+    }
+
+    pub fn cells(&self) -> &[R::Cell] {
+        &self.cells
+    }
+
+    pub fn step(&mut self) -> StepEvent {
+        self.update();
+        let fired = self.cascade();
+        for &index in &fired {
+            self.cells[index] = self.rule.reset(self.cells[index]);
+        }
+        self.steps += 1;
+
+        StepEvent {
+            step: self.steps,
+            fired: fired.iter().map(|&index| self.get_point(index)).collect(),
+        }
+    }
+
+    pub fn steps(&mut self) -> Steps<'_, R, N> {
+        Steps { automaton: self }
+    }
+
+    fn update(&mut self) {
+        let mut neighbours = Vec::with_capacity(self.neighbourhood.offsets().len());
+        for index in 0..self.cells.len() {
+            neighbours.clear();
+            neighbours.extend(self.neighbours(index).map(|n| self.cells[n]));
+            self.buffer[index] = self.rule.update(self.cells[index], &neighbours);
+        }
+        std::mem::swap(&mut self.cells, &mut self.buffer);
+    }
+
+    fn cascade(&mut self) -> Vec<usize> {
+        let mut fired = vec![false; self.cells.len()];
+        let mut order = vec![];
+        let mut queue: VecDeque<usize> = (0..self.cells.len())
+            .filter(|&index| self.rule.fires(self.cells[index]))
+            .collect();
+        for &index in &queue {
+            fired[index] = true;
+        }
+
+        while let Some(index) = queue.pop_front() {
+            order.push(index);
+            let neighbours: Vec<usize> = self.neighbours(index).collect();
+            for next in neighbours {
+                if fired[next] {
+                    continue;
+                }
+                self.cells[next] = self.rule.excite(self.cells[next]);
+                if self.rule.fires(self.cells[next]) {
+                    fired[next] = true;
+                    queue.push_back(next);
+                }
+            }
+        }
+
+        order
+    }
+
+    fn neighbours(&self, index: usize) -> impl Iterator<Item = usize> + '_ {
+        let (row, column) = self.get_point(index);
+        self.neighbourhood
+            .offsets()
+            .iter()
+            .map(move |(dr, dc)| (row + dr, column + dc))
+            .filter(move |&(r, c)| !self.out_of_bounds(r, c))
+            .map(move |(r, c)| self.get_index(r, c))
+    }
+
+    fn get_index(&self, row: i32, column: i32) -> usize {
+        (row * self.width + column) as usize
+    }
+
+    fn get_point(&self, index: usize) -> (i32, i32) {
+        (index as i32 / self.width, index as i32 % self.width)
+    }
+
+    fn out_of_bounds(&self, row: i32, column: i32) -> bool {
+        row < 0 || row >= self.height || column < 0 || column >= self.width
+    }
+}
+
+impl<'a, R: Rule, N: Neighbourhood> Iterator for Steps<'a, R, N> {
+    type Item = StepEvent;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        Some(self.automaton.step())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Life;
+
+    impl Rule for Life {
+        type Cell = bool;
+
+        fn update(&self, cell: bool, neighbours: &[bool]) -> bool {
+            let alive = neighbours.iter().filter(|n| **n).count();
+            alive == 3 || (cell && alive == 2)
+        }
+    }
+
+    fn parse_life(rows: &[&str]) -> Vec<bool> {
+        rows.iter()
+            .flat_map(|r| r.chars().map(|c| c == '#'))
+            .collect()
+    }
+
+    #[test]
+    fn test_life_blinker() {
+        // given
+        let cells = parse_life(&[".....", "..#..", "..#..", "..#..", "....."]);
+        let mut automaton = Automaton::new(cells.clone(), 5, 5, Moore, Life);
+
+        // when
+        let event = automaton.step();
+
+        // then
+        let expected = parse_life(&[".....", ".....", ".###.", ".....", "....."]);
+        assert_eq!(automaton.cells(), expected.as_slice());
+        assert!(event.fired.is_empty());
+        automaton.step();
+        assert_eq!(automaton.cells(), cells.as_slice());
+    }
+
+    #[test]
+    fn test_cascade_events() {
+        // given
+        let rule = ThresholdRule {
+            increment: 1,
+            threshold: 9,
+            reset_value: 0,
+        };
+        let cells = vec![9, 8, 1, 1, 1, 1];
+        let mut automaton = Automaton::new(cells, 3, 2, VonNeumann, rule);
+
+        // when
+        let events: Vec<StepEvent> = automaton.steps().take(2).collect();
+
+        // then
+        assert_eq!(events[0].step, 1);
+        assert_eq!(events[0].fired, vec![(0, 0), (0, 1)]);
+        assert_eq!(automaton.cells(), &[1, 1, 4, 4, 4, 3]);
+        assert_eq!(events[1].fired, vec![]);
+    }
+
+    #[test]
+    fn test_custom_neighbourhood() {
+        // given
+        let rule = ThresholdRule {
+            increment: 0,
+            threshold: 0,
+            reset_value: -5,
+        };
+        let neighbourhood = vec![(0, 1)];
+        let mut automaton = Automaton::new(vec![1, 0, 0, 0], 4, 1, neighbourhood, rule);
+
+        // when
+        let event = automaton.step();
+
+        // then
+        assert_eq!(event.fired, vec![(0, 0), (0, 1), (0, 2), (0, 3)]);
+        assert_eq!(automaton.cells(), &[-5, -5, -5, -5]);
+    }
+}
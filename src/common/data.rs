@@ -1,5 +1,7 @@
 use std::fs::File;
-use std::io::{BufRead, BufReader, Read};
+use std::io;
+use std::io::{BufRead, BufReader, BufWriter, Read, Write};
+use std::path::Path;
 
 const DEFAULT_CAPACITY: usize = 1000;
 
@@ -33,3 +35,16 @@ pub fn get_path(year: u32, day: u8, bigboy: bool) -> String {
         format!("./data/{}/day{:02}.txt", year, day)
     }
 }
+
+pub fn export<F>(dir: &Path, file_name: &str, write: F)
+where
+    F: FnOnce(&mut BufWriter<File>) -> io::Result<()>,
+{
+    let path = dir.join(file_name);
+    let file = File::create(&path).unwrap_or_else(|_| panic!("Cannot create {}", path.display()));
+    let mut out = BufWriter::new(file);
+    write(&mut out)
+        .and_then(|_| out.flush())
+        .unwrap_or_else(|e| panic!("Cannot write {}: {}", path.display(), e));
+    println!("Saved {}", path.display());
+}
@@ -0,0 +1,367 @@
+use std::fmt;
+use std::fmt::Formatter;
+use std::str::FromStr;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum JsonValue {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<JsonValue>),
+    Object(Vec<(String, JsonValue)>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JsonErrorKind {
+    UnexpectedChar(char),
+    UnexpectedEnd,
+    InvalidNumber,
+    InvalidEscape,
+    TrailingCharacters,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct JsonError {
+    pub position: usize,
+    pub kind: JsonErrorKind,
+}
+
+impl fmt::Display for JsonError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self.kind {
+            JsonErrorKind::UnexpectedChar(c) => {
+                write!(f, "unexpected '{}' at position {}", c, self.position)
+            }
+            JsonErrorKind::UnexpectedEnd => write!(f, "unexpected end of input"),
+            JsonErrorKind::InvalidNumber => {
+                write!(f, "invalid number at position {}", self.position)
+            }
+            JsonErrorKind::InvalidEscape => {
+                write!(f, "invalid escape at position {}", self.position)
+            }
+            JsonErrorKind::TrailingCharacters => {
+                write!(f, "trailing characters at position {}", self.position)
+            }
+        }
+    }
+}
+
+impl JsonValue {
+    pub fn fold<T, F>(&self, init: T, f: &mut F) -> T
+    where
+        F: FnMut(T, &JsonValue) -> T,
+    {
+        self.fold_filtered(init, &|_| true, f)
+    }
+
+    // objects rejected by `keep_object` are skipped together with everything nested inside them
+    pub fn fold_filtered<T, P, F>(&self, init: T, keep_object: &P, f: &mut F) -> T
+    where
+        P: Fn(&[(String, JsonValue)]) -> bool,
+        F: FnMut(T, &JsonValue) -> T,
+    {
+        match self {
+            JsonValue::Object(fields) if !keep_object(fields) => init,
+            JsonValue::Object(fields) => {
+                let acc = f(init, self);
+                fields
+                    .iter()
+                    .fold(acc, |acc, (_, v)| v.fold_filtered(acc, keep_object, f))
+            }
+            JsonValue::Array(values) => {
+                let acc = f(init, self);
+                values
+                    .iter()
+                    .fold(acc, |acc, v| v.fold_filtered(acc, keep_object, f))
+            }
+            _ => f(init, self),
+        }
+    }
+}
+
+impl FromStr for JsonValue {
+    type Err = JsonError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parser = Parser {
+            chars: s.chars().collect(),
+            pos: 0,
+        };
+        let value = parser.parse_value()?;
+        parser.skip_whitespace();
+        if parser.pos < parser.chars.len() {
+            return Err(parser.error(JsonErrorKind::TrailingCharacters));
+        }
+        Ok(value)
+    }
+}
+
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+}
+
+impl Parser {
+    fn error(&self, kind: JsonErrorKind) -> JsonError {
+        JsonError {
+            position: self.pos,
+            kind,
+        }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn next(&mut self) -> Result<char, JsonError> {
+        let c = self
+            .peek()
+            .ok_or_else(|| self.error(JsonErrorKind::UnexpectedEnd))?;
+        self.pos += 1;
+        Ok(c)
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), JsonError> {
+        match self.next()? {
+            c if c == expected => Ok(()),
+            c => {
+                self.pos -= 1;
+                Err(self.error(JsonErrorKind::UnexpectedChar(c)))
+            }
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        while matches!(self.peek(), Some(' ' | '\n' | '\r' | '\t')) {
+            self.pos += 1;
+        }
+    }
+
+    fn parse_value(&mut self) -> Result<JsonValue, JsonError> {
+        self.skip_whitespace();
+        match self.peek() {
+            None => Err(self.error(JsonErrorKind::UnexpectedEnd)),
+            Some('{') => self.parse_object(),
+            Some('[') => self.parse_array(),
+            Some('"') => Ok(JsonValue::String(self.parse_string()?)),
+            Some('t') => self.parse_literal("true", JsonValue::Bool(true)),
+            Some('f') => self.parse_literal("false", JsonValue::Bool(false)),
+            Some('n') => self.parse_literal("null", JsonValue::Null),
+            Some(c) if c == '-' || c.is_ascii_digit() => self.parse_number(),
+            Some(c) => Err(self.error(JsonErrorKind::UnexpectedChar(c))),
+        }
+    }
+
+    fn parse_literal(&mut self, literal: &str, value: JsonValue) -> Result<JsonValue, JsonError> {
+        for expected in literal.chars() {
+            self.expect(expected)?;
+        }
+        Ok(value)
+    }
+
+    fn parse_number(&mut self) -> Result<JsonValue, JsonError> {
+        let start = self.pos;
+        while matches!(self.peek(), Some(c) if c.is_ascii_digit() || "+-.eE".contains(c)) {
+            self.pos += 1;
+        }
+        let text: String = self.chars[start..self.pos].iter().collect();
+        text.parse().map(JsonValue::Number).map_err(|_| JsonError {
+            position: start,
+            kind: JsonErrorKind::InvalidNumber,
+        })
+    }
+
+    fn parse_string(&mut self) -> Result<String, JsonError> {
+        self.expect('"')?;
+        let mut result = String::new();
+        loop {
+            match self.next()? {
+                '"' => return Ok(result),
+                '\\' => result.push(self.parse_escape()?),
+                c => result.push(c),
+            }
+        }
+    }
+
+    fn parse_escape(&mut self) -> Result<char, JsonError> {
+        let escaped = match self.next()? {
+            '"' => '"',
+            '\\' => '\\',
+            '/' => '/',
+            'b' => '\u{8}',
+            'f' => '\u{c}',
+            'n' => '\n',
+            'r' => '\r',
+            't' => '\t',
+            'u' => return self.parse_unicode_escape(),
+            _ => {
+                self.pos -= 1;
+                return Err(self.error(JsonErrorKind::InvalidEscape));
+            }
+        };
+        Ok(escaped)
+    }
+
+    fn parse_unicode_escape(&mut self) -> Result<char, JsonError> {
+        let start = self.pos;
+        let high = self.parse_hex4()?;
+        let code = if (0xD800..0xDC00).contains(&high) {
+            self.expect('\\')?;
+            self.expect('u')?;
+            let low = self.parse_hex4()?;
+            if !(0xDC00..0xE000).contains(&low) {
+                return Err(JsonError {
+                    position: start,
+                    kind: JsonErrorKind::InvalidEscape,
+                });
+            }
+            0x10000 + ((high - 0xD800) << 10) + (low - 0xDC00)
+        } else {
+            high
+        };
+        char::from_u32(code).ok_or(JsonError {
+            position: start,
+            kind: JsonErrorKind::InvalidEscape,
+        })
+    }
+
+    fn parse_hex4(&mut self) -> Result<u32, JsonError> {
+        let mut value = 0;
+        for _ in 0..4 {
+            let digit = self.next()?;
+            let digit = digit.to_digit(16).ok_or(JsonError {
+                position: self.pos - 1,
+                kind: JsonErrorKind::InvalidEscape,
+            })?;
+            value = value * 16 + digit;
+        }
+        Ok(value)
+    }
+
+    fn parse_array(&mut self) -> Result<JsonValue, JsonError> {
+        self.expect('[')?;
+        let mut values = vec![];
+        self.skip_whitespace();
+        if self.peek() == Some(']') {
+            self.pos += 1;
+            return Ok(JsonValue::Array(values));
+        }
+
+        loop {
+            values.push(self.parse_value()?);
+            self.skip_whitespace();
+            match self.next()? {
+                ',' => continue,
+                ']' => return Ok(JsonValue::Array(values)),
+                c => {
+                    self.pos -= 1;
+                    return Err(self.error(JsonErrorKind::UnexpectedChar(c)));
+                }
+            }
+        }
+    }
+
+    fn parse_object(&mut self) -> Result<JsonValue, JsonError> {
+        self.expect('{')?;
+        let mut fields = vec![];
+        self.skip_whitespace();
+        if self.peek() == Some('}') {
+            self.pos += 1;
+            return Ok(JsonValue::Object(fields));
+        }
+
+        loop {
+            self.skip_whitespace();
+            let key = self.parse_string()?;
+            self.skip_whitespace();
+            self.expect(':')?;
+            let value = self.parse_value()?;
+            fields.push((key, value));
+            self.skip_whitespace();
+            match self.next()? {
+                ',' => continue,
+                '}' => return Ok(JsonValue::Object(fields)),
+                c => {
+                    self.pos -= 1;
+                    return Err(self.error(JsonErrorKind::UnexpectedChar(c)));
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_nested() {
+        // given
+        let input = r#" {"a": [1, -2.5e1, true, null], "b": {"c": "x\"\u00e9\ud83d\ude00"}} "#;
+
+        // when
+        let value: JsonValue = input.parse().unwrap();
+
+        // then
+        let expected = JsonValue::Object(vec![
+            (
+                "a".to_string(),
+                JsonValue::Array(vec![
+                    JsonValue::Number(1.0),
+                    JsonValue::Number(-25.0),
+                    JsonValue::Bool(true),
+                    JsonValue::Null,
+                ]),
+            ),
+            (
+                "b".to_string(),
+                JsonValue::Object(vec![(
+                    "c".to_string(),
+                    JsonValue::String("x\"é😀".to_string()),
+                )]),
+            ),
+        ]);
+        assert_eq!(value, expected);
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let cases = [
+            ("[1, 2", 5, JsonErrorKind::UnexpectedEnd),
+            ("[1; 2]", 2, JsonErrorKind::UnexpectedChar(';')),
+            ("{\"a\" 1}", 5, JsonErrorKind::UnexpectedChar('1')),
+            ("\"\\q\"", 2, JsonErrorKind::InvalidEscape),
+            ("[1-2]", 1, JsonErrorKind::InvalidNumber),
+            ("tru", 3, JsonErrorKind::UnexpectedEnd),
+            ("{} x", 3, JsonErrorKind::TrailingCharacters),
+        ];
+
+        for (input, position, kind) in cases {
+            let result = input.parse::<JsonValue>();
+            assert_eq!(result, Err(JsonError { position, kind }), "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_fold_filtered() {
+        // given
+        let value: JsonValue = r#"[1, {"skip": true, "n": 5}, {"n": 2}]"#.parse().unwrap();
+        let count_numbers = &mut |acc: usize, v: &JsonValue| match v {
+            JsonValue::Number(_) => acc + 1,
+            _ => acc,
+        };
+
+        // when
+        let all = value.fold(0, count_numbers);
+        let kept = value.fold_filtered(
+            0,
+            &|fields: &[(String, JsonValue)]| fields.iter().all(|(k, _)| k != "skip"),
+            count_numbers,
+        );
+
+        // then
+        assert_eq!(all, 3);
+        assert_eq!(kept, 2);
+    }
+}
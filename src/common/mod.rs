@@ -1,2 +1,4 @@
+pub mod automaton;
 pub mod data;
+pub mod json;
 pub mod parse;
@@ -1,4 +1,5 @@
 use std::env;
+use std::path::PathBuf;
 
 mod aoc_2015;
 mod aoc_2019;
@@ -9,9 +10,10 @@ mod common;
 use crate::common::data::{read_to_string, read_to_vec};
 
 fn main() {
-    let argv: Vec<String> = env::args().collect();
+    let mut argv: Vec<String> = env::args().collect();
+    let export = take_export_dir(&mut argv);
     if argv.len() < 3 {
-        panic!("Usage: cargo run <year> <day> <bigboy>");
+        panic!("Usage: cargo run <year> <day> <bigboy> [--export <dir>]");
     }
 
     let year: u32 = argv[1].parse().expect("Year must be a number");
@@ -26,7 +28,7 @@ fn main() {
         (2015, 3) => aoc_2015::day03::run(&common::data::read_to_string(year, day, bigboy)),
         (2015, 4) => aoc_2015::day04::run("iwrupvqb"),
         (2015, 5) => aoc_2015::day05::run(&read_to_vec(year, day, bigboy)),
-        (2015, 6) => aoc_2015::day06::run(&read_to_vec(year, day, bigboy)),
+        (2015, 6) => aoc_2015::day06::run(&read_to_vec(year, day, bigboy), export.as_deref()),
         (2015, 7) => aoc_2015::day07::run(&read_to_vec(year, day, bigboy)),
         (2015, 8) => aoc_2015::day08::run(&read_to_string(year, day, bigboy)),
         (2015, 9) => aoc_2015::day09::run(&read_to_vec(year, day, bigboy)),
@@ -34,7 +36,7 @@ fn main() {
         (2015, 11) => aoc_2015::day11::run("hxbxxyzz"),
         (2015, 12) => aoc_2015::day12::run(&read_to_string(year, day, bigboy)),
         (2015, 13) => aoc_2015::day13::run(&read_to_vec(year, day, bigboy)),
-        (2015, 14) => aoc_2015::day14::run(&read_to_vec(year, day, bigboy)),
+        (2015, 14) => aoc_2015::day14::run(&read_to_vec(year, day, bigboy), export.as_deref()),
 
         // 2019
         (2019, 1) => aoc_2019::day01::run(&common::data::read_to_vec(year, day, bigboy)),
@@ -67,3 +69,13 @@ fn main() {
         (_, _) => panic!("Not implemented"),
     }
 }
+
+fn take_export_dir(argv: &mut Vec<String>) -> Option<PathBuf> {
+    let flag = argv.iter().position(|arg| arg == "--export")?;
+    let dir = argv
+        .get(flag + 1)
+        .unwrap_or_else(|| panic!("--export needs a directory"))
+        .into();
+    argv.drain(flag..flag + 2);
+    Some(dir)
+}